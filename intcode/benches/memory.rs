@@ -0,0 +1,100 @@
+use intcode::{HashMemory, Memory, Program, StepResult, VecMemory};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const DAY_9: &str = include_str!("../../sol9/input.txt");
+const DAY_13: &str = include_str!("../../sol13/input.txt");
+const DAY_19: &str = include_str!("../../sol19/input.txt");
+
+type BenchProgram<M> = Program<VecDeque<i128>, (), M>;
+
+fn load<M: Memory<Cell = i128>>(program_str: &str) -> BenchProgram<M> {
+    Program::new(program_str.trim(), &[])
+        .unwrap()
+        .with_memory::<M>()
+}
+
+// BOOST in sensor boost mode.
+fn day_9<M: Memory<Cell = i128> + Clone>(template: &BenchProgram<M>) -> i128 {
+    let mut program = template.clone();
+    program.send_input(2);
+    program.run().unwrap()[0]
+}
+
+// Breakout played to the end by tracking the ball with the paddle.
+fn day_13<M: Memory<Cell = i128> + Clone>(template: &BenchProgram<M>) -> i128 {
+    let mut program = template.clone();
+    program.set(0, 2);
+    let (mut paddle_x, mut ball_x, mut score) = (0, 0, 0);
+    loop {
+        let (outputs, state) = program.run_until_blocked_or_done().unwrap();
+        for tile in outputs.chunks(3) {
+            match tile {
+                [-1, 0, s] => score = *s,
+                [x, _, 3] => paddle_x = *x,
+                [x, _, 4] => ball_x = *x,
+                _ => {}
+            }
+        }
+        if state == StepResult::Halted {
+            return score;
+        }
+        program.send_input((ball_x - paddle_x).signum());
+    }
+}
+
+// Tractor beam scan, spinning up a fresh program for every point.
+fn day_19<M: Memory<Cell = i128> + Clone>(template: &BenchProgram<M>) -> i128 {
+    let mut count = 0;
+    for y in 0..50 {
+        for x in 0..50 {
+            let mut program = template.clone();
+            program.send_input(x);
+            program.send_input(y);
+            count += program.run().unwrap()[0];
+        }
+    }
+    count
+}
+
+fn time<F: FnMut() -> i128>(iterations: u32, mut f: F) -> (Duration, i128) {
+    let start = Instant::now();
+    let mut result = 0;
+    for _ in 0..iterations {
+        result = f();
+    }
+    (start.elapsed() / iterations, result)
+}
+
+fn compare(
+    name: &str,
+    iterations: u32,
+    hash: &mut dyn FnMut() -> i128,
+    vec: &mut dyn FnMut() -> i128,
+) {
+    let (hash_time, hash_result) = time(iterations, hash);
+    let (vec_time, vec_result) = time(iterations, vec);
+    assert_eq!(hash_result, vec_result, "{} backends disagree", name);
+    println!(
+        "{:<8} HashMemory {:>10.3?}  VecMemory {:>10.3?}  speedup {:.2}x",
+        name,
+        hash_time,
+        vec_time,
+        hash_time.as_secs_f64() / vec_time.as_secs_f64()
+    );
+}
+
+fn main() {
+    let (hash_9, vec_9) = (load::<HashMemory>(DAY_9), load::<VecMemory>(DAY_9));
+    compare("day 9", 5, &mut || day_9(&hash_9), &mut || day_9(&vec_9));
+
+    let (hash_13, vec_13) = (load::<HashMemory>(DAY_13), load::<VecMemory>(DAY_13));
+    compare("day 13", 5, &mut || day_13(&hash_13), &mut || {
+        day_13(&vec_13)
+    });
+
+    let (hash_19, vec_19) = (load::<HashMemory>(DAY_19), load::<VecMemory>(DAY_19));
+    compare("day 19", 5, &mut || day_19(&hash_19), &mut || {
+        day_19(&vec_19)
+    });
+}
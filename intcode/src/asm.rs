@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+
+use crate::{Command, IntcodeError, IMMEDIATE, POSITION, RELATIVE};
+
+// Source format, one statement per line:
+//
+//     loop:   IN [x]              ; labels end with ':', comments start with ';'
+//             JF [x], #done       ; '#' immediate, '[..]' position, '[rb+n]' or '[r+n]' relative
+//             OUT [x]
+//             JT #1, #loop
+//     done:   HALT
+//     x:      db 0                ; data: numbers, labels and "strings"
+//
+// Mnemonics are the ones the disassembler prints and are case-insensitive. Any number
+// can be written as a label, optionally with an offset: `[buffer+2]`.
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(i128),
+    Label(String, i128),
+}
+
+#[derive(Debug, Clone)]
+struct Operand {
+    mode: u32,
+    value: Value,
+}
+
+#[derive(Debug)]
+enum Item {
+    Instruction(Command, Vec<Operand>),
+    Data(Vec<Value>),
+}
+
+#[derive(Debug)]
+struct Statement {
+    line: usize,
+    item: Item,
+}
+impl Statement {
+    fn len(&self) -> usize {
+        match &self.item {
+            Item::Instruction(_, operands) => operands.len() + 1,
+            Item::Data(values) => values.len(),
+        }
+    }
+}
+
+fn error(line: usize, message: String) -> IntcodeError {
+    IntcodeError::Assembly { line, message }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Splits on `delimiter` outside of string literals.
+fn split_outside_strings(text: &str, delimiter: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == delimiter {
+            parts.push(&text[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn parse_value(line: usize, text: &str) -> Result<Value, IntcodeError> {
+    let text = text.trim();
+    if let Ok(number) = text.parse() {
+        return Ok(Value::Number(number));
+    }
+    let (label, offset) = match text.find(['+', '-']) {
+        Some(i) => {
+            let offset = text[i + 1..]
+                .trim()
+                .parse::<i128>()
+                .map_err(|_| error(line, format!("Invalid offset in {:?}", text)))?;
+            let sign = if text[i..].starts_with('-') { -1 } else { 1 };
+            (text[..i].trim(), sign * offset)
+        }
+        None => (text, 0),
+    };
+    if !is_identifier(label) {
+        return Err(error(line, format!("Invalid value {:?}", text)));
+    }
+    Ok(Value::Label(String::from(label), offset))
+}
+
+fn parse_operand(line: usize, text: &str) -> Result<Operand, IntcodeError> {
+    let text = text.trim();
+    if let Some(value) = text.strip_prefix('#') {
+        return Ok(Operand {
+            mode: IMMEDIATE,
+            value: parse_value(line, value)?,
+        });
+    }
+    let inner = match text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        Some(inner) => inner.trim(),
+        None => return Err(error(line, format!("Invalid operand {:?}", text))),
+    };
+    for base in ["rb", "r"].iter() {
+        if let Some(offset) = inner.strip_prefix(base) {
+            let offset = offset.trim();
+            if offset.is_empty() {
+                return Ok(Operand {
+                    mode: RELATIVE,
+                    value: Value::Number(0),
+                });
+            }
+            if let Some(sign) = offset.chars().next().filter(|&c| c == '+' || c == '-') {
+                let value = match (sign, parse_value(line, &offset[1..])?) {
+                    ('-', Value::Number(n)) => match n.checked_neg() {
+                        Some(n) => Value::Number(n),
+                        None => return Err(error(line, format!("Invalid offset in {:?}", text))),
+                    },
+                    ('-', Value::Label(..)) => {
+                        return Err(error(
+                            line,
+                            format!("Cannot subtract a label from rb in {:?}", text),
+                        ))
+                    }
+                    (_, value) => value,
+                };
+                return Ok(Operand {
+                    mode: RELATIVE,
+                    value,
+                });
+            }
+        }
+    }
+    Ok(Operand {
+        mode: POSITION,
+        value: parse_value(line, inner)?,
+    })
+}
+
+fn parse_string(line: usize, text: &str) -> Result<Vec<Value>, IntcodeError> {
+    let mut values = vec![];
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(c @ '\\') | Some(c @ '"') => c,
+                other => return Err(error(line, format!("Invalid escape \\{:?}", other))),
+            }
+        } else {
+            c
+        };
+        values.push(Value::Number(c as i128));
+    }
+    Ok(values)
+}
+
+fn parse_statement(line: usize, text: &str) -> Result<Option<Item>, IntcodeError> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let (mnemonic, rest) = match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], text[i..].trim()),
+        None => (text, ""),
+    };
+
+    if mnemonic.eq_ignore_ascii_case("db") {
+        let mut values = vec![];
+        for part in split_outside_strings(rest, ',') {
+            let part = part.trim();
+            if part.len() >= 2 && part.starts_with('"') && part.ends_with('"') {
+                values.extend(parse_string(line, part)?);
+            } else {
+                values.push(parse_value(line, part)?);
+            }
+        }
+        return Ok(Some(Item::Data(values)));
+    }
+
+    let command = Command::from_mnemonic(mnemonic)
+        .ok_or_else(|| error(line, format!("Unknown mnemonic {:?}", mnemonic)))?;
+    let operands = if rest.is_empty() {
+        vec![]
+    } else {
+        rest.split(',')
+            .map(|operand| parse_operand(line, operand))
+            .collect::<Result<Vec<Operand>, IntcodeError>>()?
+    };
+    if operands.len() != command.num_params() {
+        return Err(error(
+            line,
+            format!(
+                "{} takes {} operands but got {}",
+                command.mnemonic(),
+                command.num_params(),
+                operands.len()
+            ),
+        ));
+    }
+    Ok(Some(Item::Instruction(command, operands)))
+}
+
+fn resolve(
+    line: usize,
+    value: &Value,
+    labels: &HashMap<String, usize>,
+) -> Result<i128, IntcodeError> {
+    match value {
+        Value::Number(n) => Ok(*n),
+        Value::Label(label, offset) => match labels.get(label) {
+            Some(&address) => (address as i128)
+                .checked_add(*offset)
+                .ok_or_else(|| error(line, format!("{}{:+} is out of range", label, offset))),
+            None => Err(error(line, format!("Undefined label {:?}", label))),
+        },
+    }
+}
+
+pub fn assemble_words(source: &str) -> Result<Vec<i128>, IntcodeError> {
+    let mut statements = vec![];
+    let mut labels = HashMap::new();
+    let mut address = 0;
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let mut text = split_outside_strings(text, ';')[0].trim();
+        while let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if !is_identifier(label) {
+                break;
+            }
+            if labels.insert(String::from(label), address).is_some() {
+                return Err(error(line, format!("Duplicate label {:?}", label)));
+            }
+            text = text[colon + 1..].trim();
+        }
+        if let Some(item) = parse_statement(line, text)? {
+            let statement = Statement { line, item };
+            address += statement.len();
+            statements.push(statement);
+        }
+    }
+
+    let mut words = Vec::with_capacity(address);
+    for statement in statements.iter() {
+        match &statement.item {
+            Item::Instruction(command, operands) => {
+                let mut opcode = command.code();
+                let mut place = 100;
+                for operand in operands.iter() {
+                    opcode += operand.mode as i128 * place;
+                    place *= 10;
+                }
+                words.push(opcode);
+                for operand in operands.iter() {
+                    words.push(resolve(statement.line, &operand.value, &labels)?);
+                }
+            }
+            Item::Data(values) => {
+                for value in values.iter() {
+                    words.push(resolve(statement.line, value, &labels)?);
+                }
+            }
+        }
+    }
+    Ok(words)
+}
+
+/// Assembles source into the comma-separated form `Program::new` takes.
+pub fn assemble(source: &str) -> Result<String, IntcodeError> {
+    Ok(assemble_words(source)?
+        .iter()
+        .map(|word| word.to_string())
+        .collect::<Vec<String>>()
+        .join(","))
+}
@@ -0,0 +1,36 @@
+use intcode::{Debugger, Program};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::process;
+
+/// Instructions the debugger can step back through.
+const HISTORY: usize = 100_000;
+
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("Usage: debug <program file> [command file]");
+        process::exit(2);
+    }
+    let program = Program::from_path(&args[1], &[]).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let program = program.with_history(HISTORY);
+    let mut debugger = Debugger::new(program);
+    let result = match args.get(2) {
+        Some(path) => match File::open(path) {
+            Ok(file) => debugger.repl(BufReader::new(file), io::stdout()),
+            Err(e) => {
+                eprintln!("Could not read {}: {}", path, e);
+                process::exit(1);
+            }
+        },
+        None => debugger.repl(io::stdin().lock(), io::stdout()),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,23 @@
+use intcode::{decompile, read_program, ControlFlowGraph, Disassembly};
+use std::env;
+use std::process;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: decompile <program file>");
+            process::exit(2);
+        }
+    };
+    match read_program(&path) {
+        Ok(words) => print!(
+            "{}",
+            decompile(&ControlFlowGraph::new(&Disassembly::new(words)))
+        ),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use intcode::{read_program, ControlFlowGraph, Disassembly};
+use std::env;
+use std::process;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let (dot, path) = match &args[..] {
+        [path] => (false, path),
+        [flag, path] if flag == "--dot" => (true, path),
+        _ => {
+            eprintln!("Usage: disasm [--dot] <program file>");
+            process::exit(2);
+        }
+    };
+    match read_program(path) {
+        Ok(words) if dot => print!("{}", ControlFlowGraph::new(&Disassembly::new(words)).dot()),
+        Ok(words) => print!("{}", Disassembly::new(words)),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use intcode::Fuzzer;
+use std::env;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CASES: usize = 10_000;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    let parsed = match &args[..] {
+        [] => Some((CASES, None)),
+        [cases] => cases.parse().ok().map(|cases| (cases, None)),
+        [cases, seed] => match (cases.parse(), seed.parse()) {
+            (Ok(cases), Ok(seed)) => Some((cases, Some(seed))),
+            _ => None,
+        },
+        _ => None,
+    };
+    let (cases, seed) = match parsed {
+        Some(parsed) => parsed,
+        None => {
+            eprintln!("Usage: fuzz [cases [seed]]");
+            process::exit(2);
+        }
+    };
+    let seed = seed.unwrap_or_else(|| {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        now.as_nanos() as u64
+    });
+
+    println!("Fuzzing {} cases from seed {}", cases, seed);
+    match Fuzzer::new().seed(seed).cases(cases).run() {
+        Ok(passed) => println!("{} cases passed", passed),
+        Err(failure) => {
+            println!("{}", failure);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use intcode::Program;
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str =
+    "Usage: profile <program file> [--json] [--top n] [--ascii <text file>] [inputs...]";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| fail(format!("Could not read {}: {}", path, e)))
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args.next().unwrap_or_else(|| fail(String::from(USAGE)));
+    let mut json = false;
+    let mut top = 10;
+    let mut inputs = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--top" => {
+                top = match args.next().map(|n| n.parse()) {
+                    Some(Ok(n)) => n,
+                    _ => fail(String::from(USAGE)),
+                }
+            }
+            "--ascii" => match args.next() {
+                Some(text_path) => inputs.extend(read(&text_path).chars().map(|c| c as i128)),
+                None => fail(String::from(USAGE)),
+            },
+            input => match input.parse() {
+                Ok(input) => inputs.push(input),
+                Err(_) => fail(format!("Not a number: {}\n{}", input, USAGE)),
+            },
+        }
+    }
+
+    let mut program = Program::from_path(&path, &inputs)
+        .unwrap_or_else(|e| fail(e.to_string()))
+        .with_profiler();
+    if let Err(e) = program.run_until_blocked_or_done() {
+        eprintln!("Stopped on error: {}", e);
+    }
+    let profile = program.take_profile().unwrap();
+    if json {
+        println!("{}", profile.to_json(top));
+    } else {
+        print!("{}", profile.report(top));
+    }
+}
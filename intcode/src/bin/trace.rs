@@ -0,0 +1,74 @@
+use intcode::{read_program, replay, Program, Trace, TraceWriter};
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::process;
+use std::sync::{Arc, Mutex};
+
+const USAGE: &str = "Usage:
+    trace record <program file> <trace file> [inputs...]
+    trace replay <program file> <trace file>
+    trace diff <trace file> <trace file>";
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| fail(format!("Could not read {}: {}", path, e)))
+}
+
+fn read_trace(path: &str) -> Trace {
+    Trace::parse(&read(path)).unwrap_or_else(|e| fail(format!("{}: {}", path, e)))
+}
+
+fn record(program_path: &str, trace_path: &str, inputs: &[String]) {
+    let inputs = inputs
+        .iter()
+        .map(|input| {
+            input
+                .parse()
+                .unwrap_or_else(|_| fail(format!("Not a number: {}", input)))
+        })
+        .collect::<Vec<i128>>();
+    let file = File::create(trace_path)
+        .unwrap_or_else(|e| fail(format!("Could not create {}: {}", trace_path, e)));
+    let writer = Arc::new(Mutex::new(TraceWriter::new(BufWriter::new(file))));
+    let mut program = Program::from_path(program_path, &inputs)
+        .unwrap_or_else(|e| fail(e.to_string()))
+        .with_trace(writer.clone());
+    let (outputs, state) = program
+        .run_until_blocked_or_done()
+        .unwrap_or_else(|e| fail(e.to_string()));
+    if let Err(e) = writer.lock().unwrap().flush() {
+        fail(format!("Could not write {}: {}", trace_path, e));
+    }
+    println!("{:?}", outputs);
+    if !state.is_halted() {
+        println!("Stopped waiting for input");
+    }
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    let divergence = match (args.get(1).map(String::as_str), args.len()) {
+        (Some("record"), n) if n >= 4 => {
+            record(&args[2], &args[3], &args[4..]);
+            return;
+        }
+        (Some("replay"), 4) => {
+            let cells = read_program(&args[2]).unwrap_or_else(|e| fail(e.to_string()));
+            replay(cells, &read_trace(&args[3])).unwrap_or_else(|e| fail(e.to_string()))
+        }
+        (Some("diff"), 4) => read_trace(&args[2]).first_divergence(&read_trace(&args[3])),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    match divergence {
+        Some(divergence) => fail(divergence.to_string()),
+        None => println!("Traces match"),
+    }
+}
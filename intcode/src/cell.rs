@@ -0,0 +1,118 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+#[cfg(feature = "bigint")]
+use num_traits::{ToPrimitive, Zero};
+
+/// A value a memory cell can hold. Programs run on `i128` unless a memory backend
+/// with another cell type is chosen, e.g. `VecMemory<i64>`.
+pub trait Cell:
+    Clone + Default + PartialEq + PartialOrd + fmt::Debug + fmt::Display + FromStr + Send + 'static
+{
+    fn from_i128(value: i128) -> Option<Self>;
+
+    fn to_i128(&self) -> Option<i128>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    fn wrapping_add(&self, other: &Self) -> Self;
+
+    fn wrapping_mul(&self, other: &Self) -> Self;
+
+    fn from_bool(value: bool) -> Self {
+        Self::from_i128(value as i128).unwrap()
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+macro_rules! primitive_cell {
+    ($($t:ty),*) => {$(
+        impl Cell for $t {
+            fn from_i128(value: i128) -> Option<Self> {
+                <$t>::try_from(value).ok()
+            }
+
+            fn to_i128(&self) -> Option<i128> {
+                Some(*self as i128)
+            }
+
+            fn checked_add(&self, other: &Self) -> Option<Self> {
+                <$t>::checked_add(*self, *other)
+            }
+
+            fn checked_mul(&self, other: &Self) -> Option<Self> {
+                <$t>::checked_mul(*self, *other)
+            }
+
+            fn wrapping_add(&self, other: &Self) -> Self {
+                <$t>::wrapping_add(*self, *other)
+            }
+
+            fn wrapping_mul(&self, other: &Self) -> Self {
+                <$t>::wrapping_mul(*self, *other)
+            }
+        }
+    )*};
+}
+
+primitive_cell!(i32, i64, i128);
+
+/// Never overflows, so checked and wrapping arithmetic agree.
+#[cfg(feature = "bigint")]
+impl Cell for BigInt {
+    fn from_i128(value: i128) -> Option<Self> {
+        Some(BigInt::from(value))
+    }
+
+    fn to_i128(&self) -> Option<i128> {
+        ToPrimitive::to_i128(self)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn wrapping_add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn wrapping_mul(&self, other: &Self) -> Self {
+        self * other
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+}
+
+/// The cell as an `i128`, clamped to `i128::MIN` or `i128::MAX` if it doesn't fit.
+/// Only used to fill in error fields.
+pub(crate) fn saturate<C: Cell>(value: &C) -> i128 {
+    match value.to_i128() {
+        Some(value) => value,
+        None if *value < C::default() => i128::MIN,
+        None => i128::MAX,
+    }
+}
+
+/// What ADD and MULTIPLY do when a result doesn't fit in the cell type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Arithmetic {
+    /// Stop with `IntcodeError::Overflow`.
+    #[default]
+    Checked,
+    /// Wrap around, as two's complement hardware would.
+    Wrapping,
+}
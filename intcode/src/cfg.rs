@@ -0,0 +1,361 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::disasm::return_site;
+use crate::{Command, Disassembly, Flow, Instruction, Operand, Target};
+
+/// How a basic block hands over control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Runs on into the next instruction, which starts another block.
+    Next,
+    Jump(Target),
+    /// Jumps to the target or runs on into the next instruction.
+    Branch(Target),
+    /// Stores `return_site` as the return address and jumps to a subroutine, or
+    /// through a function pointer when the entry is computed.
+    Call {
+        entry: Target,
+        return_site: usize,
+    },
+    /// Jumps to a return address kept relative to the relative base.
+    Return,
+    Halt,
+}
+
+/// A run of instructions only ever entered at the first and left after the last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<Instruction>,
+    pub exit: Exit,
+}
+impl Block {
+    /// The address just past the last instruction.
+    pub fn end(&self) -> usize {
+        let last = &self.instructions[self.instructions.len() - 1];
+        last.address + last.len()
+    }
+
+    fn last(&self) -> &Instruction {
+        &self.instructions[self.instructions.len() - 1]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Running on, or a conditional jump not taken.
+    Next,
+    Jump,
+    Call,
+    /// From a call to where its subroutine returns to.
+    Resume,
+}
+
+/// Control passing from the block at `from` to the block at `to`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// Code entered through calls, found by the usual call sequence: a constant return
+/// address stored just before an unconditional jump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subroutine {
+    pub entry: usize,
+    /// Addresses of the jumps that call it.
+    pub callers: Vec<usize>,
+    /// Start of every block reachable from the entry without following calls.
+    pub blocks: BTreeSet<usize>,
+    /// The stack frame the entry allocates with an immediate ARB.
+    pub frame: Option<i128>,
+    /// Whether every path moves the relative base by known amounts and returns with
+    /// it back where it was on entry.
+    pub balanced: bool,
+}
+
+/// The basic blocks of a disassembled program and the edges between them.
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+    blocks: BTreeMap<usize, Block>,
+    edges: Vec<Edge>,
+    subroutines: BTreeMap<usize, Subroutine>,
+}
+impl ControlFlowGraph {
+    pub fn new(disassembly: &Disassembly) -> Self {
+        let blocks = split_blocks(disassembly);
+        let mut edges = vec![];
+        for block in blocks.values() {
+            let next = block.end();
+            let mut add = |to: usize, kind: EdgeKind| {
+                if blocks.contains_key(&to) {
+                    edges.push(Edge {
+                        from: block.start,
+                        to,
+                        kind,
+                    });
+                }
+            };
+            match block.exit {
+                Exit::Next => add(next, EdgeKind::Next),
+                Exit::Jump(Target::Known(target)) => add(target, EdgeKind::Jump),
+                Exit::Branch(target) => {
+                    add(next, EdgeKind::Next);
+                    if let Target::Known(target) = target {
+                        add(target, EdgeKind::Jump);
+                    }
+                }
+                Exit::Call { entry, return_site } => {
+                    if let Target::Known(entry) = entry {
+                        add(entry, EdgeKind::Call);
+                    }
+                    add(return_site, EdgeKind::Resume);
+                }
+                Exit::Jump(Target::Computed) | Exit::Return | Exit::Halt => {}
+            }
+        }
+
+        let mut graph = ControlFlowGraph {
+            blocks,
+            edges,
+            subroutines: BTreeMap::new(),
+        };
+        let mut callers = BTreeMap::<usize, Vec<usize>>::new();
+        for block in graph.blocks.values() {
+            if let Exit::Call {
+                entry: Target::Known(entry),
+                ..
+            } = block.exit
+            {
+                callers.entry(entry).or_default().push(block.last().address);
+            }
+        }
+        graph.subroutines = callers
+            .into_iter()
+            .map(|(entry, callers)| (entry, graph.subroutine(entry, callers)))
+            .collect();
+        graph
+    }
+
+    /// Blocks in address order.
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.blocks.values()
+    }
+
+    pub fn block_at(&self, start: usize) -> Option<&Block> {
+        self.blocks.get(&start)
+    }
+
+    /// The block holding the instruction at `address`.
+    pub fn block_containing(&self, address: usize) -> Option<&Block> {
+        let (_, block) = self.blocks.range(..=address).next_back()?;
+        Some(block).filter(|block| address < block.end())
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn successors(&self, start: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == start)
+    }
+
+    pub fn predecessors(&self, start: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == start)
+    }
+
+    /// Subroutines in order of their entry points.
+    pub fn subroutines(&self) -> impl Iterator<Item = &Subroutine> {
+        self.subroutines.values()
+    }
+
+    pub fn subroutine_at(&self, entry: usize) -> Option<&Subroutine> {
+        self.subroutines.get(&entry)
+    }
+
+    /// Addresses of jumps whose target is read from memory, other than returns and calls
+    /// through function pointers. Code
+    /// only they lead to is missing from the graph; see `Disassembly::with_entry_points`.
+    pub fn computed_jumps(&self) -> Vec<usize> {
+        self.blocks
+            .values()
+            .filter(|block| {
+                matches!(
+                    block.exit,
+                    Exit::Jump(Target::Computed) | Exit::Branch(Target::Computed)
+                )
+            })
+            .map(|block| block.last().address)
+            .collect()
+    }
+
+    /// The graph in Graphviz DOT, with each subroutine drawn as a cluster.
+    pub fn dot(&self) -> String {
+        let mut dot = String::from("digraph intcode {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        let mut clustered = BTreeSet::new();
+        for subroutine in self.subroutines.values() {
+            let frame = match subroutine.frame {
+                Some(frame) => format!(", frame {}", frame),
+                None => String::new(),
+            };
+            writeln!(dot, "    subgraph cluster_{} {{", subroutine.entry).unwrap();
+            writeln!(dot, "        label=\"sub_{}{}\";", subroutine.entry, frame).unwrap();
+            for &start in subroutine.blocks.iter() {
+                // A block shared by two subroutines can only be drawn in one.
+                if clustered.insert(start) {
+                    writeln!(dot, "        {}", self.node(&self.blocks[&start])).unwrap();
+                }
+            }
+            dot.push_str("    }\n");
+        }
+        for block in self.blocks.values() {
+            if !clustered.contains(&block.start) {
+                writeln!(dot, "    {}", self.node(block)).unwrap();
+            }
+        }
+        for edge in self.edges.iter() {
+            let style = match edge.kind {
+                EdgeKind::Next => "",
+                EdgeKind::Jump => " [color=blue]",
+                EdgeKind::Call => " [style=bold, label=\"call\"]",
+                EdgeKind::Resume => " [style=dashed]",
+            };
+            writeln!(dot, "    b{} -> b{}{};", edge.from, edge.to, style).unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn node(&self, block: &Block) -> String {
+        let mut label = String::new();
+        for instruction in block.instructions.iter() {
+            write!(label, "{}: {}\\l", instruction.address, instruction).unwrap();
+        }
+        let color = match block.exit {
+            Exit::Jump(Target::Computed) | Exit::Branch(Target::Computed) => ", color=red",
+            Exit::Return => ", color=darkgreen",
+            _ => "",
+        };
+        format!("b{} [label=\"{}\"{}];", block.start, label, color)
+    }
+
+    /// Walks the blocks of the subroutine at `entry`, tracking how far the relative
+    /// base has moved since entry. A call is assumed to leave it where it was.
+    fn subroutine(&self, entry: usize, callers: Vec<usize>) -> Subroutine {
+        let mut offsets = BTreeMap::<usize, Option<i128>>::new();
+        let mut balanced = true;
+        let mut pending = vec![(entry, Some(0))];
+        while let Some((start, offset)) = pending.pop() {
+            let block = match self.blocks.get(&start) {
+                Some(block) => block,
+                None => continue,
+            };
+            if let Some(&seen) = offsets.get(&start) {
+                balanced &= seen == offset;
+                continue;
+            }
+            offsets.insert(start, offset);
+
+            let mut offset = offset;
+            for instruction in block.instructions.iter() {
+                if instruction.command() == Command::REL {
+                    offset = match (offset, instruction.operands[0]) {
+                        (Some(offset), Operand::Immediate(change)) => offset.checked_add(change),
+                        _ => None,
+                    };
+                }
+            }
+            balanced &= offset.is_some();
+            match block.exit {
+                Exit::Return => balanced &= offset == Some(0),
+                Exit::Call { return_site, .. } => pending.push((return_site, offset)),
+                _ => pending.extend(self.successors(start).map(|edge| (edge.to, offset))),
+            }
+        }
+
+        let frame = self.blocks.get(&entry).and_then(|block| {
+            let first = &block.instructions[0];
+            match first.operands[..] {
+                [Operand::Immediate(frame)] if first.command() == Command::REL => Some(frame),
+                _ => None,
+            }
+        });
+        Subroutine {
+            entry,
+            callers,
+            blocks: offsets.keys().copied().collect(),
+            frame,
+            balanced,
+        }
+    }
+}
+
+/// Cuts the reachable instructions into blocks: one starts at every jump target,
+/// return site and after every instruction that doesn't just run on.
+fn split_blocks(disassembly: &Disassembly) -> BTreeMap<usize, Block> {
+    let words = disassembly.words();
+    let mut leaders = BTreeSet::new();
+    let mut previous: Option<&Instruction> = None;
+    for instruction in disassembly.instructions() {
+        if let Flow::Jump(Target::Known(target)) | Flow::Branch(Target::Known(target)) =
+            instruction.flow()
+        {
+            leaders.insert(target);
+        }
+        leaders.extend(return_site(words, instruction));
+        let runs_on = previous.is_some_and(|previous| {
+            previous.flow() == Flow::Next
+                && previous.address + previous.len() == instruction.address
+        });
+        if !runs_on {
+            leaders.insert(instruction.address);
+        }
+        previous = Some(instruction);
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut current: Vec<Instruction> = vec![];
+    let instructions = disassembly.instructions().collect::<Vec<&Instruction>>();
+    for (index, &instruction) in instructions.iter().enumerate() {
+        current.push(instruction.clone());
+        let next = instruction.address + instruction.len();
+        let ends_block = match instructions.get(index + 1) {
+            Some(following) => following.address != next || leaders.contains(&next),
+            None => true,
+        };
+        if instruction.flow() == Flow::Next && !ends_block {
+            continue;
+        }
+        let exit = exit(words, &current, instruction);
+        let start = current[0].address;
+        blocks.insert(
+            start,
+            Block {
+                start,
+                instructions: std::mem::take(&mut current),
+                exit,
+            },
+        );
+    }
+    blocks
+}
+
+fn exit(words: &[i128], block: &[Instruction], last: &Instruction) -> Exit {
+    let jump = match last.flow() {
+        Flow::Next => return Exit::Next,
+        Flow::Halt => return Exit::Halt,
+        Flow::Branch(target) => return Exit::Branch(target),
+        Flow::Jump(target) => target,
+    };
+    let store = block.len().checked_sub(2).map(|index| &block[index]);
+    match (store.and_then(|store| return_site(words, store)), jump) {
+        (Some(return_site), entry) => Exit::Call { entry, return_site },
+        (None, Target::Computed) if matches!(last.operands[1], Operand::Relative(_)) => {
+            Exit::Return
+        }
+        (None, target) => Exit::Jump(target),
+    }
+}
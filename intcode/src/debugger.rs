@@ -0,0 +1,436 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    Checkpoint, Flow, Instruction, IntcodeError, Memory, Operand, OutputSink, Program, StepResult,
+    VecMemory,
+};
+
+const HELP: &str = "\
+s, step [n]        execute n instructions (default 1)
+c, continue        run until a breakpoint, watchpoint, halt or input wait
+finish             run until the current subroutine returns
+rs, rstep [n]      step back n instructions (default 1)
+rwrite <addr>      step back to just before the last write to an address
+checkpoint         remember this point in history
+rewind <n>         go back to checkpoint n
+b, break <addr>    set a breakpoint
+delete <addr>      remove a breakpoint
+watch <addr>       stop after any write to an address
+unwatch <addr>     remove a watchpoint
+info               show ip, relative base, breakpoints and watchpoints
+inputs             show queued inputs
+input <n>...       queue inputs
+ascii <text>       queue text followed by a newline
+x <addr> [count]   show memory
+set <addr> <value> write memory
+l, list [addr] [n] disassemble n instructions from addr (default ip)
+outputs            show all outputs so far
+q, quit            leave the debugger";
+
+/// Why the debugger handed control back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    /// One instruction ran.
+    Step,
+    /// The ip reached a breakpoint; the instruction there has not run yet.
+    Breakpoint(usize),
+    /// The instruction at `ip` wrote to a watched address.
+    Watchpoint {
+        ip: usize,
+        address: usize,
+        old: i128,
+        new: i128,
+    },
+    /// `finish` saw the current subroutine return.
+    Returned,
+    NeedsInput,
+    Halted,
+}
+
+/// Runs a program under control of breakpoints and watchpoints. Stepping backwards
+/// needs a program made `with_history`.
+pub struct Debugger<O = (), M: Memory<Cell = i128> = VecMemory> {
+    program: Program<VecDeque<i128>, O, M>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+    checkpoints: Vec<Checkpoint>,
+    outputs: Vec<i128>,
+}
+impl<O: OutputSink, M: Memory<Cell = i128>> Debugger<O, M> {
+    pub fn new(program: Program<VecDeque<i128>, O, M>) -> Self {
+        Debugger {
+            program,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            checkpoints: vec![],
+            outputs: vec![],
+        }
+    }
+
+    pub fn program(&self) -> &Program<VecDeque<i128>, O, M> {
+        &self.program
+    }
+
+    pub fn program_mut(&mut self) -> &mut Program<VecDeque<i128>, O, M> {
+        &mut self.program
+    }
+
+    pub fn into_program(self) -> Program<VecDeque<i128>, O, M> {
+        self.program
+    }
+
+    pub fn outputs(&self) -> &[i128] {
+        &self.outputs
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address)
+    }
+
+    fn write_address(&self, instruction: &Instruction) -> Option<usize> {
+        let address = match instruction.write_operand()? {
+            Operand::Position(address) | Operand::Immediate(address) => address,
+            Operand::Relative(offset) => self.program.relative_base().checked_add(offset)?,
+        };
+        usize::try_from(address).ok()
+    }
+
+    pub fn step(&mut self) -> Result<Stop, IntcodeError> {
+        let ip = self.program.ip();
+        let watched = self
+            .program
+            .current_instruction()
+            .and_then(|instruction| self.write_address(&instruction))
+            .filter(|address| self.watchpoints.contains(address));
+        let old = watched.map(|address| self.program.memory().get(address));
+
+        match self.program.execute()? {
+            StepResult::NeedsInput => return Ok(Stop::NeedsInput),
+            StepResult::Halted => return Ok(Stop::Halted),
+            StepResult::Output(output) => self.outputs.push(output),
+            StepResult::Continued => {}
+        }
+        if let (Some(address), Some(old)) = (watched, old) {
+            return Ok(Stop::Watchpoint {
+                ip,
+                address,
+                old,
+                new: self.program.memory().get(address),
+            });
+        }
+        Ok(Stop::Step)
+    }
+
+    /// Undoes the last instruction. False if there's no history left to undo.
+    pub fn step_back(&mut self) -> bool {
+        match self.program.step_back() {
+            Some(StepResult::Output(_)) => {
+                self.outputs.pop();
+                true
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /// Goes back to just before the last write to `address`. False if it isn't in
+    /// the history.
+    pub fn run_back_to_write(&mut self, address: usize) -> bool {
+        let taken_back = self.program.run_back_to_write(address);
+        self.take_back(taken_back)
+    }
+
+    pub fn rewind(&mut self, checkpoint: Checkpoint) -> bool {
+        let taken_back = self.program.rewind(checkpoint);
+        self.take_back(taken_back)
+    }
+
+    fn take_back(&mut self, outputs: Option<Vec<i128>>) -> bool {
+        match outputs {
+            Some(outputs) => {
+                let kept = self.outputs.len().saturating_sub(outputs.len());
+                self.outputs.truncate(kept);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Runs at least one instruction, so continuing from a breakpoint moves past it.
+    pub fn cont(&mut self) -> Result<Stop, IntcodeError> {
+        loop {
+            let stop = self.step()?;
+            if stop != Stop::Step {
+                return Ok(stop);
+            }
+            if self.breakpoints.contains(&self.program.ip()) {
+                return Ok(Stop::Breakpoint(self.program.ip()));
+            }
+        }
+    }
+
+    /// Runs until the current subroutine returns: the relative base drops below its
+    /// value on entry to `finish` and the next taken jump goes back to the caller.
+    pub fn finish(&mut self) -> Result<Stop, IntcodeError> {
+        let base = self.program.relative_base();
+        let mut returning = false;
+        loop {
+            let instruction = self.program.current_instruction();
+            let stop = self.step()?;
+            if stop != Stop::Step {
+                return Ok(stop);
+            }
+            if self.program.relative_base() < base {
+                returning = true;
+            }
+            if let Some(instruction) = instruction {
+                let jumped = self.program.ip() != instruction.address + instruction.len();
+                if returning && jumped && instruction.flow() != Flow::Next {
+                    return Ok(Stop::Returned);
+                }
+            }
+            if self.breakpoints.contains(&self.program.ip()) {
+                return Ok(Stop::Breakpoint(self.program.ip()));
+            }
+        }
+    }
+
+    fn describe(&self, stop: &Stop) -> String {
+        match stop {
+            Stop::Step => String::new(),
+            Stop::Breakpoint(address) => format!("Breakpoint at {}\n", address),
+            Stop::Watchpoint {
+                ip,
+                address,
+                old,
+                new,
+            } => format!("Watchpoint: [{}] {} -> {} at {}\n", address, old, new, ip),
+            Stop::Returned => String::from("Returned\n"),
+            Stop::NeedsInput => String::from("Waiting for input\n"),
+            Stop::Halted => String::from("Halted\n"),
+        }
+    }
+
+    fn location(&self) -> String {
+        let ip = self.program.ip();
+        match self.program.current_instruction() {
+            Some(instruction) => format!("{:>6}: {}", ip, instruction),
+            None => format!("{:>6}: db {}", ip, self.program.memory().get(ip)),
+        }
+    }
+
+    fn run_command(&mut self, words: &[&str]) -> Result<String, String> {
+        let number = |i: usize| -> Result<i128, String> {
+            match words.get(i) {
+                Some(word) => word.parse().map_err(|_| format!("Not a number: {}", word)),
+                None => Err(format!("{} needs an argument", words[0])),
+            }
+        };
+        let address = |i: usize| -> Result<usize, String> {
+            let value = number(i)?;
+            usize::try_from(value).map_err(|_| format!("Not an address: {}", value))
+        };
+        let stop_report = |debugger: &Self, stop: Result<Stop, IntcodeError>| match stop {
+            Ok(stop) => Ok(format!(
+                "{}{}",
+                debugger.describe(&stop),
+                debugger.location()
+            )),
+            Err(e) => Err(format!("Error: {}", e)),
+        };
+
+        match words[0] {
+            "s" | "step" => {
+                let count = if words.len() > 1 { number(1)? } else { 1 };
+                let mut stop = Ok(Stop::Step);
+                for _ in 0..count {
+                    stop = self.step();
+                    if stop != Ok(Stop::Step) {
+                        break;
+                    }
+                }
+                stop_report(self, stop)
+            }
+            "c" | "continue" => {
+                let stop = self.cont();
+                stop_report(self, stop)
+            }
+            "finish" => {
+                let stop = self.finish();
+                stop_report(self, stop)
+            }
+            "rs" | "rstep" => {
+                let count = if words.len() > 1 { number(1)? } else { 1 };
+                for stepped in 0..count {
+                    if !self.step_back() {
+                        if stepped == 0 {
+                            return Err(String::from("No history to step back through"));
+                        }
+                        break;
+                    }
+                }
+                Ok(self.location())
+            }
+            "rwrite" => match self.run_back_to_write(address(1)?) {
+                true => Ok(format!(
+                    "Last write to [{}]\n{}",
+                    address(1)?,
+                    self.location()
+                )),
+                false => Err(format!("No write to [{}] in history", address(1)?)),
+            },
+            "checkpoint" => match self.program.checkpoint() {
+                Some(checkpoint) => {
+                    self.checkpoints.push(checkpoint);
+                    Ok(format!(
+                        "Checkpoint {} at step {}",
+                        self.checkpoints.len() - 1,
+                        checkpoint.steps()
+                    ))
+                }
+                None => Err(String::from("No history kept for this program")),
+            },
+            "rewind" => {
+                let index = address(1)?;
+                let checkpoint = *self
+                    .checkpoints
+                    .get(index)
+                    .ok_or_else(|| format!("No checkpoint {}", index))?;
+                match self.rewind(checkpoint) {
+                    true => Ok(self.location()),
+                    false => Err(format!("Checkpoint {} is no longer in history", index)),
+                }
+            }
+            "b" | "break" => {
+                self.add_breakpoint(address(1)?);
+                Ok(format!("Breakpoint set at {}", address(1)?))
+            }
+            "delete" => match self.remove_breakpoint(address(1)?) {
+                true => Ok(format!("Deleted breakpoint at {}", address(1)?)),
+                false => Err(format!("No breakpoint at {}", address(1)?)),
+            },
+            "watch" => {
+                self.add_watchpoint(address(1)?);
+                Ok(format!("Watching [{}]", address(1)?))
+            }
+            "unwatch" => match self.remove_watchpoint(address(1)?) {
+                true => Ok(format!("Stopped watching [{}]", address(1)?)),
+                false => Err(format!("Not watching [{}]", address(1)?)),
+            },
+            "info" => Ok(format!(
+                "ip {}, relative base {}\nbreakpoints {:?}\nwatchpoints {:?}\n{}",
+                self.program.ip(),
+                self.program.relative_base(),
+                self.breakpoints,
+                self.watchpoints,
+                self.location()
+            )),
+            "inputs" => Ok(format!("{:?}", self.program.input())),
+            "input" => {
+                for i in 1..words.len() {
+                    let value = number(i)?;
+                    self.program.send_input(value);
+                }
+                Ok(format!("{:?}", self.program.input()))
+            }
+            "ascii" => {
+                for c in words[1..].join(" ").chars().chain("\n".chars()) {
+                    self.program.send_input(c as i128);
+                }
+                Ok(format!("Queued {} inputs", self.program.num_inputs()))
+            }
+            "x" => {
+                let start = address(1)?;
+                let count = if words.len() > 2 { address(2)? } else { 1 };
+                let last = start
+                    .checked_add(count.saturating_sub(1))
+                    .ok_or_else(|| format!("Not an address: {} + {}", start, count))?;
+                let values = (start..=last)
+                    .take(count)
+                    .map(|a| self.program.memory().get(a).to_string())
+                    .collect::<Vec<String>>();
+                Ok(format!("[{}] {}", start, values.join(" ")))
+            }
+            "set" => {
+                let (target, value) = (address(1)?, number(2)?);
+                self.program.set(target, value);
+                Ok(format!("[{}] = {}", target, value))
+            }
+            "l" | "list" => {
+                let mut next = if words.len() > 1 {
+                    address(1)?
+                } else {
+                    self.program.ip()
+                };
+                let count = if words.len() > 2 { address(2)? } else { 5 };
+                let mut lines = vec![];
+                for _ in 0..count {
+                    match Instruction::decode_memory(self.program.memory(), next) {
+                        Some(instruction) => {
+                            lines.push(format!("{:>6}: {}", next, instruction));
+                            next = match next.checked_add(instruction.len()) {
+                                Some(next) => next,
+                                None => break,
+                            };
+                        }
+                        None => {
+                            lines.push(format!(
+                                "{:>6}: db {}",
+                                next,
+                                self.program.memory().get(next)
+                            ));
+                            next = match next.checked_add(1) {
+                                Some(next) => next,
+                                None => break,
+                            };
+                        }
+                    }
+                }
+                Ok(lines.join("\n"))
+            }
+            "outputs" => Ok(format!("{:?}", self.outputs)),
+            "help" => Ok(String::from(HELP)),
+            command => Err(format!("Unknown command {:?}, try help", command)),
+        }
+    }
+
+    /// Reads commands line by line until `quit` or the end of `input`, writing responses
+    /// to `output`. Works the same on stdin or a script file.
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        writeln!(output, "{}", self.location())?;
+        for line in input.lines() {
+            let line = line?;
+            let words = line.split_whitespace().collect::<Vec<&str>>();
+            if words.is_empty() || words[0].starts_with('#') {
+                continue;
+            }
+            if words[0] == "q" || words[0] == "quit" {
+                break;
+            }
+            let seen_outputs = self.outputs.len();
+            let response = self.run_command(&words);
+            for value in self.outputs.iter().skip(seen_outputs) {
+                writeln!(output, "Output {}", value)?;
+            }
+            match response {
+                Ok(text) => writeln!(output, "{}", text)?,
+                Err(text) => writeln!(output, "{}", text)?,
+            }
+        }
+        Ok(())
+    }
+}
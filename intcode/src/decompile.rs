@@ -0,0 +1,868 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{Command, ControlFlowGraph, Exit, Instruction, Operand, Target};
+
+const INDENT: &str = "    ";
+/// Stands in for the single exit of a function when finding post-dominators.
+const END: usize = usize::MAX;
+
+/// Lifts a program into C-like pseudo-code: one function for the code entered at
+/// address 0 and one for each subroutine.
+///
+/// Relative-base stack frames become variables. In a subroutine `v1`, `v2`, ... are
+/// the slots of its frame counted from the return address, and in any function `arg1`,
+/// `arg2`, ... are the slots just past the top of its frame, where arguments for a call
+/// and the values it hands back go. A compare followed by a jump on its result becomes
+/// a single condition, and the result the compare stores is dropped. Whatever doesn't
+/// fit an `if` or a `while` is left as a `goto`.
+pub fn decompile(graph: &ControlFlowGraph) -> String {
+    // A call can land in the middle of an instruction, where no block starts.
+    let mut functions = vec![];
+    if graph.block_at(0).is_some() {
+        functions.push(Function::new(graph, 0));
+    }
+    functions.extend(
+        graph
+            .subroutines()
+            .filter(|subroutine| subroutine.entry != 0)
+            .filter(|subroutine| graph.block_at(subroutine.entry).is_some())
+            .map(|subroutine| Function::new(graph, subroutine.entry)),
+    );
+
+    let mut arities = BTreeMap::<usize, usize>::new();
+    for function in functions.iter() {
+        for (entry, arguments) in function.calls() {
+            let arity = arities.entry(entry).or_default();
+            *arity = (*arity).max(arguments);
+        }
+    }
+
+    let mut source = String::new();
+    for function in functions.iter() {
+        if !source.is_empty() {
+            source.push('\n');
+        }
+        function.write(
+            &mut source,
+            arities.get(&function.entry).copied().unwrap_or(0),
+        );
+    }
+    source
+}
+
+/// The condition a conditional jump tests, and its negation.
+struct Condition {
+    holds: String,
+    fails: String,
+}
+
+#[derive(Debug)]
+struct Loop {
+    body: BTreeSet<usize>,
+    exit: Option<usize>,
+}
+
+enum Line {
+    Text(usize, String),
+    /// Where a block starts, shown only if something jumps to it with a `goto`.
+    Label(usize),
+}
+
+struct Function<'a> {
+    graph: &'a ControlFlowGraph,
+    entry: usize,
+    /// Every block of the function and how far the relative base has moved since
+    /// entry when it starts, if that's known.
+    offsets: BTreeMap<usize, Option<i128>>,
+    post_dominators: BTreeMap<usize, usize>,
+    loops: BTreeMap<usize, Loop>,
+}
+impl<'a> Function<'a> {
+    fn new(graph: &'a ControlFlowGraph, entry: usize) -> Self {
+        let mut function = Function {
+            graph,
+            entry,
+            offsets: BTreeMap::new(),
+            post_dominators: BTreeMap::new(),
+            loops: BTreeMap::new(),
+        };
+        let mut pending = vec![(entry, Some(0))];
+        while let Some((start, offset)) = pending.pop() {
+            if function.offsets.contains_key(&start) {
+                continue;
+            }
+            function.offsets.insert(start, offset);
+            let offset = function.offset_after(start, offset);
+            pending.extend(
+                function
+                    .successors(start)
+                    .into_iter()
+                    .map(|next| (next, offset)),
+            );
+        }
+
+        let dominators = immediate_dominators(entry, |block| function.successors(block));
+        let exits = function
+            .offsets
+            .keys()
+            .copied()
+            .filter(|&block| function.successors(block).is_empty())
+            .collect::<Vec<usize>>();
+        function.post_dominators = immediate_dominators(END, |block| match block {
+            END => exits.clone(),
+            _ => function.predecessors(block),
+        });
+
+        let mut bodies = BTreeMap::<usize, BTreeSet<usize>>::new();
+        for &block in function.offsets.keys() {
+            for header in function.successors(block) {
+                if dominates(&dominators, header, block) {
+                    let body = function.loop_body(header, block);
+                    bodies.entry(header).or_default().extend(body);
+                }
+            }
+        }
+        function.loops = bodies
+            .into_iter()
+            .map(|(header, body)| {
+                let exit = function.loop_exit(header, &body);
+                (header, Loop { body, exit })
+            })
+            .collect();
+        function
+    }
+
+    fn block_exit(&self, start: usize) -> Exit {
+        self.graph.block_at(start).unwrap().exit
+    }
+
+    /// Where control can go next without leaving the function. Calls come back to
+    /// their return site.
+    fn successors(&self, start: usize) -> Vec<usize> {
+        let block = match self.graph.block_at(start) {
+            Some(block) => block,
+            None => return vec![],
+        };
+        let next = block.end();
+        let candidates = match block.exit {
+            Exit::Next => vec![next],
+            Exit::Jump(Target::Known(target)) => vec![target],
+            Exit::Branch(Target::Known(target)) => vec![target, next],
+            Exit::Branch(Target::Computed) => vec![next],
+            Exit::Call { return_site, .. } => vec![return_site],
+            Exit::Jump(Target::Computed) | Exit::Return | Exit::Halt => vec![],
+        };
+        candidates
+            .into_iter()
+            .filter(|&target| self.graph.block_at(target).is_some())
+            .collect()
+    }
+
+    fn predecessors(&self, start: usize) -> Vec<usize> {
+        self.offsets
+            .keys()
+            .copied()
+            .filter(|&block| self.successors(block).contains(&start))
+            .collect()
+    }
+
+    fn offset_after(&self, start: usize, mut offset: Option<i128>) -> Option<i128> {
+        for instruction in self.graph.block_at(start).unwrap().instructions.iter() {
+            offset = moved(offset, instruction);
+        }
+        offset
+    }
+
+    /// The blocks of the natural loop closed by the jump from `tail` back to `header`.
+    fn loop_body(&self, header: usize, tail: usize) -> BTreeSet<usize> {
+        let mut body = BTreeSet::new();
+        body.insert(header);
+        let mut pending = vec![tail];
+        while let Some(block) = pending.pop() {
+            if body.insert(block) {
+                pending.extend(self.predecessors(block));
+            }
+        }
+        body
+    }
+
+    /// Where a loop carries on once it's done: the way out of a test at the top if
+    /// there is one, otherwise the lowest address it can leave to.
+    fn loop_exit(&self, header: usize, body: &BTreeSet<usize>) -> Option<usize> {
+        let outside = |block: &usize| !body.contains(block);
+        if let Exit::Branch(_) = self.block_exit(header) {
+            if let Some(exit) = self.successors(header).into_iter().find(outside) {
+                return Some(exit);
+            }
+        }
+        body.iter()
+            .flat_map(|&block| self.successors(block))
+            .filter(outside)
+            .min()
+    }
+
+    /// Calls this function makes, with how many arguments each passes.
+    fn calls(&self) -> Vec<(usize, usize)> {
+        self.offsets
+            .iter()
+            .filter_map(|(&start, &offset)| match self.block_exit(start) {
+                Exit::Call {
+                    entry: Target::Known(entry),
+                    ..
+                } => {
+                    let (_, arguments) = self.call_arguments(start, offset);
+                    Some((entry, arguments.len()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn write(&self, source: &mut String, arity: usize) {
+        if self.entry == 0 {
+            source.push_str("void main() {\n");
+        } else {
+            let subroutine = self.graph.subroutine_at(self.entry).unwrap();
+            let mut notes = vec![];
+            if let Some(frame) = subroutine.frame {
+                notes.push(format!("frame {}", frame));
+            }
+            if !subroutine.balanced {
+                notes.push(String::from("unbalanced stack"));
+            }
+            let callers = subroutine
+                .callers
+                .iter()
+                .map(|caller| caller.to_string())
+                .collect::<Vec<String>>();
+            notes.push(format!("called from {}", callers.join(", ")));
+            source.push_str(&format!("// {}\n", notes.join(", ")));
+            let parameters = (1..=arity)
+                .map(|slot| format!("v{}", slot))
+                .collect::<Vec<String>>();
+            source.push_str(&format!(
+                "void sub_{}({}) {{\n",
+                self.entry,
+                parameters.join(", ")
+            ));
+        }
+
+        let mut writer = Writer {
+            function: self,
+            lines: vec![],
+            depth: 1,
+            emitted: BTreeSet::new(),
+            gotos: BTreeSet::new(),
+            loops: vec![],
+        };
+        writer.region(self.entry, None);
+        for line in writer.lines.iter() {
+            match line {
+                Line::Text(depth, text) => {
+                    source.push_str(&INDENT.repeat(*depth));
+                    source.push_str(text);
+                    source.push('\n');
+                }
+                Line::Label(block) if writer.gotos.contains(block) => {
+                    source.push_str(&format!("L{}:\n", block));
+                }
+                Line::Label(_) => {}
+            }
+        }
+        source.push_str("}\n");
+    }
+
+    fn operand(&self, operand: Operand, offset: Option<i128>) -> String {
+        match (operand, offset) {
+            (Operand::Immediate(value), _) => value.to_string(),
+            (Operand::Position(address), _) => format!("mem[{}]", address),
+            (Operand::Relative(slot), None) => format!("rb[{}]", slot),
+            (Operand::Relative(slot), Some(_)) if slot > 0 => format!("arg{}", slot),
+            (Operand::Relative(slot), Some(offset)) => match offset.checked_add(slot) {
+                None => format!("rb[{}]", slot),
+                Some(address) if self.entry == 0 => format!("mem[{}]", address),
+                Some(0) => String::from("ret"),
+                Some(slot) if slot > 0 => format!("v{}", slot),
+                Some(slot) => format!("caller[{}]", slot),
+            },
+        }
+    }
+
+    /// The value an instruction that writes computes, if it's free of side effects.
+    fn value(&self, instruction: &Instruction, offset: Option<i128>) -> Option<String> {
+        let operand = |index: usize| self.operand(instruction.operands[index], offset);
+        let is =
+            |index: usize, value: i128| instruction.operands[index] == Operand::Immediate(value);
+        Some(match instruction.command() {
+            _ if instruction.constant_result().is_some() => {
+                instruction.constant_result().unwrap().to_string()
+            }
+            Command::ADD if is(1, 0) => operand(0),
+            Command::ADD if is(0, 0) => operand(1),
+            Command::ADD => match instruction.operands[1] {
+                Operand::Immediate(value) if value < 0 => match value.checked_neg() {
+                    Some(value) => format!("{} - {}", operand(0), value),
+                    None => format!("{} + {}", operand(0), operand(1)),
+                },
+                _ => format!("{} + {}", operand(0), operand(1)),
+            },
+            Command::MULTIPLY if is(1, 1) => operand(0),
+            Command::MULTIPLY if is(0, 1) => operand(1),
+            Command::MULTIPLY if is(1, -1) => format!("-{}", operand(0)),
+            Command::MULTIPLY if is(0, -1) => format!("-{}", operand(1)),
+            Command::MULTIPLY => format!("{} * {}", operand(0), operand(1)),
+            Command::LESS => format!("{} < {}", operand(0), operand(1)),
+            Command::EQUALS => format!("{} == {}", operand(0), operand(1)),
+            _ => return None,
+        })
+    }
+
+    fn statement(&self, instruction: &Instruction, offset: Option<i128>) -> Option<String> {
+        let operand = |index: usize| self.operand(instruction.operands[index], offset);
+        if let Some(value) = self.value(instruction, offset) {
+            let target = instruction.write_operand().unwrap();
+            return Some(format!("{} = {};", self.operand(target, offset), value));
+        }
+        Some(match instruction.command() {
+            Command::INPUT => format!("{} = input();", operand(0)),
+            Command::OUTPUT => format!("output({});", operand(0)),
+            Command::REL => match instruction.operands[0] {
+                Operand::Immediate(_) => return None,
+                _ => format!("rb += {};", operand(0)),
+            },
+            Command::CUSTOM(_) => {
+                let operands = (0..instruction.operands.len())
+                    .map(operand)
+                    .collect::<Vec<String>>();
+                format!("{}({});", instruction.mnemonic(), operands.join(", "))
+            }
+            // Jumps and halts end their block and are written with it.
+            _ => return None,
+        })
+    }
+
+    /// Where the stores of a call's arguments start in its block, as they don't need
+    /// statements of their own, and the arguments in order.
+    fn call_arguments(&self, start: usize, offset: Option<i128>) -> (usize, Vec<String>) {
+        let instructions = &self.graph.block_at(start).unwrap().instructions;
+        let offset = self.offset_after(start, offset);
+        // The last two are the store of the return address and the jump.
+        let mut first = instructions.len() - 2;
+        let mut stored = BTreeMap::new();
+        while first > 0 {
+            let instruction = &instructions[first - 1];
+            let slot = match instruction.write_operand() {
+                Some(Operand::Relative(slot)) if slot > 0 && !stored.contains_key(&slot) => slot,
+                _ => break,
+            };
+            match self.value(instruction, offset) {
+                Some(value) => stored.insert(slot, value),
+                None => break,
+            };
+            first -= 1;
+        }
+        // A slot in between that isn't stored here passes on what's already in it.
+        let count = stored.keys().next_back().copied().unwrap_or(0);
+        let arguments = (1..=count)
+            .map(|slot| {
+                stored
+                    .remove(&slot)
+                    .unwrap_or_else(|| format!("arg{}", slot))
+            })
+            .collect();
+        (first, arguments)
+    }
+
+    /// What a block's final conditional jump tests, folding in a compare just before
+    /// it. Returns how many instructions are left to write as statements.
+    fn condition(&self, start: usize, offset: Option<i128>) -> (usize, Condition) {
+        let instructions = &self.graph.block_at(start).unwrap().instructions;
+        let offset = self.offset_after(start, offset);
+        let jump = &instructions[instructions.len() - 1];
+        let on_nonzero = jump.command() == Command::JIT;
+        let mut kept = instructions.len() - 1;
+        let flag = jump.operands[0];
+        let mut test = (self.operand(flag, offset), None);
+        if let Some(compare) = instructions.len().checked_sub(2).map(|i| &instructions[i]) {
+            let negated = match compare.command() {
+                Command::LESS => Some(">="),
+                Command::EQUALS => Some("!="),
+                _ => None,
+            };
+            if let (Some(negated), Some(target)) = (negated, compare.write_operand()) {
+                if target == flag {
+                    let left = self.operand(compare.operands[0], offset);
+                    let right = self.operand(compare.operands[1], offset);
+                    test = (
+                        self.value(compare, offset).unwrap(),
+                        Some(format!("{} {} {}", left, negated, right)),
+                    );
+                    kept -= 1;
+                }
+            }
+        }
+        let (nonzero, zero) = match test {
+            (test, Some(negated)) => (test, negated),
+            (test, None) => (test.clone(), format!("!{}", test)),
+        };
+        let condition = if on_nonzero {
+            Condition {
+                holds: nonzero,
+                fails: zero,
+            }
+        } else {
+            Condition {
+                holds: zero,
+                fails: nonzero,
+            }
+        };
+        (kept, condition)
+    }
+}
+
+struct OpenLoop {
+    header: usize,
+    exit: Option<usize>,
+    /// How many `continue`s its body has so far.
+    continues: usize,
+}
+
+struct Writer<'a, 'b> {
+    function: &'b Function<'a>,
+    lines: Vec<Line>,
+    depth: usize,
+    emitted: BTreeSet<usize>,
+    gotos: BTreeSet<usize>,
+    /// The loops being written, innermost last.
+    loops: Vec<OpenLoop>,
+}
+impl Writer<'_, '_> {
+    fn line(&mut self, text: String) {
+        if text.ends_with("continue;") {
+            if let Some(open) = self.loops.last_mut() {
+                open.continues += 1;
+            }
+        }
+        self.lines.push(Line::Text(self.depth, text));
+    }
+
+    /// `break` or `continue` if jumping to `target` means leaving or restarting the
+    /// innermost loop.
+    fn loop_jump(&self, target: usize) -> Option<&'static str> {
+        let open = self.loops.last()?;
+        if open.header == target {
+            Some("continue;")
+        } else if open.exit == Some(target) {
+            Some("break;")
+        } else {
+            None
+        }
+    }
+
+    /// Writes the code from `start` up to `follow`, where the caller carries on.
+    fn region(&mut self, start: usize, follow: Option<usize>) {
+        let mut current = Some(start);
+        while let Some(block) = current {
+            if current == follow {
+                return;
+            }
+            if let Some(jump) = self.loop_jump(block) {
+                self.line(String::from(jump));
+                return;
+            }
+            if !self.function.offsets.contains_key(&block) {
+                // Jumping to something the disassembler couldn't decode.
+                self.line(format!("goto *{};", block));
+                return;
+            }
+            if self.emitted.contains(&block) {
+                self.gotos.insert(block);
+                self.line(format!("goto L{};", block));
+                return;
+            }
+            self.emitted.insert(block);
+            current = match self.function.loops.get(&block) {
+                Some(lp) => self.looped(block, lp.exit),
+                None => self.block(block, follow),
+            };
+        }
+    }
+
+    fn looped(&mut self, header: usize, exit: Option<usize>) -> Option<usize> {
+        let function = self.function;
+        let offset = function.offsets[&header];
+        let successors = function.successors(header);
+        let test_only = match function.block_exit(header) {
+            Exit::Branch(Target::Known(_)) => function.condition(header, offset).0 == 0,
+            _ => false,
+        };
+        let leaves = successors.len() == 2 && exit.is_some_and(|exit| successors.contains(&exit));
+        let inner = successors.iter().copied().find(|&next| Some(next) != exit);
+        self.lines.push(Line::Label(header));
+        self.loops.push(OpenLoop {
+            header,
+            exit,
+            continues: 0,
+        });
+        match inner {
+            Some(inner) if test_only && leaves => {
+                let (_, condition) = function.condition(header, offset);
+                let taken = successors[0] == inner;
+                let test = if taken {
+                    condition.holds
+                } else {
+                    condition.fails
+                };
+                self.line(format!("while ({}) {{", test));
+                self.depth += 1;
+                self.region(inner, None);
+                self.drop_continue();
+            }
+            _ => {
+                let opening = self.lines.len();
+                self.line(String::from("while (true) {"));
+                self.depth += 1;
+                let next = self.block(header, None);
+                if let Some(next) = next {
+                    self.region(next, None);
+                }
+                self.drop_continue();
+                if let Some(test) = self.trailing_test() {
+                    self.depth -= 1;
+                    self.lines[opening] = Line::Text(self.depth, String::from("do {"));
+                    self.line(format!("}} while ({});", test));
+                    self.loops.pop();
+                    return exit;
+                }
+            }
+        }
+        self.depth -= 1;
+        self.line(String::from("}"));
+        self.loops.pop();
+        exit
+    }
+
+    /// Drops a `continue` the loop body ends with anyway.
+    fn drop_continue(&mut self) {
+        if let Some(Line::Text(depth, last)) = self.lines.last() {
+            if *depth == self.depth && last == "continue;" {
+                self.lines.pop();
+                self.loops.last_mut().unwrap().continues -= 1;
+            }
+        }
+    }
+
+    /// Takes the test off a loop body ending `if (test) continue; break;`, so the loop
+    /// can be written as a `do`-`while`. Any other `continue` would skip to the test.
+    fn trailing_test(&mut self) -> Option<String> {
+        if self.loops.last()?.continues != 1 {
+            return None;
+        }
+        let count = self.lines.len();
+        let test = match &self.lines[count.checked_sub(2)?..] {
+            [Line::Text(depth, test), Line::Text(_, last)]
+                if *depth == self.depth && last == "break;" =>
+            {
+                test.strip_prefix("if (")?.strip_suffix(") continue;")?
+            }
+            _ => return None,
+        };
+        let test = test.to_string();
+        self.lines.truncate(count - 2);
+        Some(test)
+    }
+
+    /// Writes one block, and the branches of an `if` it ends with. Returns the block
+    /// to carry on with.
+    fn block(&mut self, start: usize, follow: Option<usize>) -> Option<usize> {
+        let function = self.function;
+        let block = function.graph.block_at(start).unwrap();
+        let offset = function.offsets[&start];
+        let mut kept = block.instructions.len();
+        let mut condition = None;
+        let mut call = None;
+        match block.exit {
+            Exit::Branch(Target::Known(_)) => {
+                let (count, test) = function.condition(start, offset);
+                kept = count;
+                condition = Some(test);
+            }
+            Exit::Call { .. } => {
+                let (count, arguments) = function.call_arguments(start, offset);
+                kept = count;
+                call = Some(arguments);
+            }
+            _ => {}
+        }
+
+        if !self.function.loops.contains_key(&start) {
+            self.lines.push(Line::Label(start));
+        }
+        let mut moved_offset = offset;
+        for instruction in block.instructions[..kept].iter() {
+            if let Some(statement) = function.statement(instruction, moved_offset) {
+                self.line(statement);
+            }
+            moved_offset = moved(moved_offset, instruction);
+        }
+        let jump = &block.instructions[block.instructions.len() - 1];
+        let target = || function.operand(jump.operands[1], moved_offset);
+        let next = block.end();
+        match (block.exit, call, condition) {
+            (Exit::Call { entry, .. }, Some(arguments), _) => {
+                let callee = match entry {
+                    Target::Known(entry) => format!("sub_{}", entry),
+                    Target::Computed => format!("(*{})", target()),
+                };
+                self.line(format!("{}({});", callee, arguments.join(", ")));
+                function.successors(start).first().copied()
+            }
+            (Exit::Branch(Target::Known(taken)), _, Some(condition)) => {
+                self.branch(start, taken, next, condition, follow)
+            }
+            (Exit::Branch(Target::Computed), _, _) => {
+                let (_, condition) = function.condition(start, offset);
+                self.line(format!("if ({}) goto *{};", condition.holds, target()));
+                Some(next)
+            }
+            (Exit::Jump(Target::Computed), _, _) => {
+                self.line(format!("goto *{};", target()));
+                None
+            }
+            (Exit::Return, _, _) => {
+                self.line(String::from("return;"));
+                None
+            }
+            (Exit::Halt, _, _) => {
+                self.line(String::from("halt();"));
+                None
+            }
+            _ => function.successors(start).first().copied(),
+        }
+    }
+
+    fn branch(
+        &mut self,
+        start: usize,
+        taken: usize,
+        next: usize,
+        condition: Condition,
+        follow: Option<usize>,
+    ) -> Option<usize> {
+        if let Some(jump) = self.loop_jump(taken) {
+            self.line(format!("if ({}) {}", condition.holds, jump));
+            return Some(next);
+        }
+        if let Some(jump) = self.loop_jump(next) {
+            self.line(format!("if ({}) {}", condition.fails, jump));
+            return Some(taken);
+        }
+
+        let join = self.join(start, follow);
+        let (taken, next) = (self.forwarded(taken), self.forwarded(next));
+        let (mut taken, mut next, mut condition) = (taken, next, condition);
+        let mut keyword = "if";
+        // Running on comes first, as the jump usually skips over it to an `else`.
+        loop {
+            if join == Some(next) {
+                self.line(format!("{} ({}) {{", keyword, condition.holds));
+                self.nested(taken, join);
+                break;
+            }
+            self.line(format!("{} ({}) {{", keyword, condition.fails));
+            self.nested(next, join);
+            if join == Some(taken) {
+                break;
+            }
+            match self.chained(taken, join) {
+                Some(link) => (taken, next, condition) = link,
+                None => {
+                    self.line(String::from("} else {"));
+                    let written = self.lines.len();
+                    self.nested(taken, join);
+                    if self.lines[written..]
+                        .iter()
+                        .all(|line| matches!(line, Line::Label(_)))
+                    {
+                        self.lines.remove(written - 1);
+                    }
+                    break;
+                }
+            }
+            keyword = "} else if";
+        }
+        self.line(String::from("}"));
+        join
+    }
+
+    /// Where a jump to `start` really goes, past blocks that do nothing but jump on.
+    fn forwarded(&self, mut start: usize) -> usize {
+        let function = self.function;
+        for _ in 0..function.offsets.len() {
+            let block = match function.graph.block_at(start) {
+                Some(block) => block,
+                None => break,
+            };
+            match block.exit {
+                Exit::Jump(Target::Known(target))
+                    if block.instructions.len() == 1
+                        && !function.loops.contains_key(&start)
+                        && function.offsets.contains_key(&target) =>
+                {
+                    start = target
+                }
+                _ => break,
+            }
+        }
+        start
+    }
+
+    /// Where both sides of the branch at `start` meet again: where every path from it
+    /// goes, unless that's out of the loop being written, in which case they end in a
+    /// `break` or `goto` anyway.
+    fn join(&self, start: usize, follow: Option<usize>) -> Option<usize> {
+        self.function
+            .post_dominators
+            .get(&start)
+            .copied()
+            .filter(|&join| join != END)
+            .filter(|join| match self.loops.last() {
+                Some(open) => self.function.loops[&open.header].body.contains(join),
+                None => true,
+            })
+            .or(follow)
+    }
+
+    /// Takes the block at `start` as the next `else if` when it's nothing but a test
+    /// whose sides meet at the same `join`.
+    fn chained(&mut self, start: usize, join: Option<usize>) -> Option<(usize, usize, Condition)> {
+        let function = self.function;
+        let block = function.graph.block_at(start)?;
+        let taken = match block.exit {
+            Exit::Branch(Target::Known(taken)) => taken,
+            _ => return None,
+        };
+        let (kept, condition) = function.condition(start, function.offsets[&start]);
+        let (taken, next) = (self.forwarded(taken), self.forwarded(block.end()));
+        if kept > 0
+            || self.emitted.contains(&start)
+            || function.loops.contains_key(&start)
+            || self
+                .loop_jump(start)
+                .or(self.loop_jump(taken))
+                .or(self.loop_jump(next))
+                .is_some()
+            || self.join(start, join) != join
+        {
+            return None;
+        }
+        self.emitted.insert(start);
+        self.lines.push(Line::Label(start));
+        Some((taken, next, condition))
+    }
+
+    fn nested(&mut self, start: usize, follow: Option<usize>) {
+        self.depth += 1;
+        self.region(start, follow);
+        self.depth -= 1;
+    }
+}
+
+fn moved(offset: Option<i128>, instruction: &Instruction) -> Option<i128> {
+    match (instruction.command(), instruction.operands.first()) {
+        (Command::REL, Some(Operand::Immediate(change))) => offset?.checked_add(*change),
+        (Command::REL, _) => None,
+        _ => offset,
+    }
+}
+
+/// Immediate dominators of everything reachable from `root`, by Cooper, Harvey and
+/// Kennedy's iterative algorithm over reverse postorder.
+fn immediate_dominators(
+    root: usize,
+    successors: impl Fn(usize) -> Vec<usize>,
+) -> BTreeMap<usize, usize> {
+    let mut order = vec![];
+    let mut seen = BTreeSet::new();
+    seen.insert(root);
+    let mut stack = vec![(root, successors(root), 0)];
+    while let Some((node, next, index)) = stack.last_mut() {
+        match next.get(*index) {
+            Some(&child) => {
+                *index += 1;
+                if seen.insert(child) {
+                    stack.push((child, successors(child), 0));
+                }
+            }
+            None => {
+                order.push(*node);
+                stack.pop();
+            }
+        }
+    }
+    order.reverse();
+    let rank = order
+        .iter()
+        .enumerate()
+        .map(|(rank, &node)| (node, rank))
+        .collect::<BTreeMap<usize, usize>>();
+    let mut predecessors = BTreeMap::<usize, Vec<usize>>::new();
+    for &node in order.iter() {
+        for child in successors(node) {
+            predecessors.entry(child).or_default().push(node);
+        }
+    }
+
+    let mut dominators = BTreeMap::new();
+    dominators.insert(root, root);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &node in order.iter().skip(1) {
+            let mut dominator = None;
+            for &predecessor in predecessors.get(&node).into_iter().flatten() {
+                if dominators.contains_key(&predecessor) {
+                    dominator = Some(match dominator {
+                        None => predecessor,
+                        Some(other) => intersect(predecessor, other, &dominators, &rank),
+                    });
+                }
+            }
+            if let Some(dominator) = dominator {
+                if dominators.insert(node, dominator) != Some(dominator) {
+                    changed = true;
+                }
+            }
+        }
+    }
+    dominators
+}
+
+fn intersect(
+    mut a: usize,
+    mut b: usize,
+    dominators: &BTreeMap<usize, usize>,
+    rank: &BTreeMap<usize, usize>,
+) -> usize {
+    while a != b {
+        while rank[&a] > rank[&b] {
+            a = dominators[&a];
+        }
+        while rank[&b] > rank[&a] {
+            b = dominators[&b];
+        }
+    }
+    a
+}
+
+fn dominates(dominators: &BTreeMap<usize, usize>, dominator: usize, mut node: usize) -> bool {
+    loop {
+        if node == dominator {
+            return true;
+        }
+        match dominators.get(&node) {
+            Some(&parent) if parent != node => node = parent,
+            _ => return false,
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::{
+    parse_program, Cell, Command, IntcodeError, Memory, Opcode, OpcodeRegistry, IMMEDIATE, POSITION,
+};
+
+const DATA_WORDS_PER_LINE: usize = 4;
+const WORDS_COLUMN_WIDTH: usize = 28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Position(i128),
+    Immediate(i128),
+    Relative(i128),
+}
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Position(address) => write!(f, "[{}]", address),
+            Operand::Immediate(value) => write!(f, "#{}", value),
+            Operand::Relative(offset) if *offset < 0 => write!(f, "[r-{}]", offset.unsigned_abs()),
+            Operand::Relative(offset) => write!(f, "[r+{}]", offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// An immediate address that is known without running the program.
+    Known(usize),
+    /// An address read from memory when the jump runs.
+    Computed,
+}
+
+/// Where control can go after an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Always continues with the next instruction.
+    Next,
+    /// Always jumps.
+    Jump(Target),
+    /// Either jumps or continues, depending on a value only known at runtime.
+    Branch(Target),
+    Halt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: usize,
+    pub opcode: i128,
+    pub operands: Vec<Operand>,
+    command: Command,
+    mnemonic: &'static str,
+}
+#[allow(clippy::len_without_is_empty)]
+impl Instruction {
+    /// Decodes the instruction starting at `address`, or returns `None` if the words
+    /// there are not a complete, well-formed instruction.
+    pub fn decode(words: &[i128], address: usize) -> Option<Instruction> {
+        Instruction::decode_with::<i128>(words, address, None)
+    }
+
+    fn decode_with<C>(
+        words: &[i128],
+        address: usize,
+        registry: Option<&OpcodeRegistry<C>>,
+    ) -> Option<Instruction> {
+        let raw = *words.get(address)?;
+        let opcode = Opcode::decode(raw, address, |code| registry?.command(code)).ok()?;
+        let operands = opcode
+            .modes()
+            .iter()
+            .enumerate()
+            .map(|(offset, &mode)| {
+                let value = *words.get(address + offset + 1)?;
+                Some(match mode {
+                    POSITION => Operand::Position(value),
+                    IMMEDIATE => Operand::Immediate(value),
+                    _ => Operand::Relative(value),
+                })
+            })
+            .collect::<Option<Vec<Operand>>>()?;
+        Some(Instruction {
+            address,
+            opcode: raw,
+            operands,
+            command: opcode.command,
+            mnemonic: match (opcode.command, registry) {
+                (Command::CUSTOM(custom), Some(registry)) => registry.mnemonic(custom.code),
+                (command, _) => command.mnemonic(),
+            },
+        })
+    }
+
+    /// Decodes the instruction starting at `address` in a running program's memory.
+    /// An operand too wide for an `i128` makes the instruction undecodable.
+    pub fn decode_memory<M: Memory>(memory: &M, address: usize) -> Option<Instruction> {
+        Instruction::decode_cells(memory, address, None)
+    }
+
+    /// Like `decode_memory`, also knowing the opcodes in `registry`.
+    pub(crate) fn decode_cells<M: Memory>(
+        memory: &M,
+        address: usize,
+        registry: Option<&OpcodeRegistry<M::Cell>>,
+    ) -> Option<Instruction> {
+        let custom = |code| registry?.command(code);
+        let opcode = Opcode::from_cell(&memory.get(address), address, custom).ok()?;
+        let window = (0..=opcode.modes().len())
+            .map(|offset| memory.get(address + offset).to_i128())
+            .collect::<Option<Vec<i128>>>()?;
+        let mut instruction = Instruction::decode_with(&window, 0, registry)?;
+        instruction.address = address;
+        Some(instruction)
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        self.mnemonic
+    }
+
+    /// Number of words the instruction occupies, including the opcode.
+    pub fn len(&self) -> usize {
+        self.operands.len() + 1
+    }
+
+    /// The operand the instruction writes to, if any.
+    pub fn write_operand(&self) -> Option<Operand> {
+        Some(self.operands[self.command.write_param()?])
+    }
+
+    pub fn flow(&self) -> Flow {
+        let jumps_if = |jump_on_nonzero: bool| {
+            let target = match self.operands[1] {
+                Operand::Immediate(address) => match usize::try_from(address) {
+                    Ok(address) => Target::Known(address),
+                    Err(_) => Target::Computed,
+                },
+                _ => Target::Computed,
+            };
+            match self.operands[0] {
+                Operand::Immediate(value) if (value != 0) == jump_on_nonzero => Flow::Jump(target),
+                Operand::Immediate(_) => Flow::Next,
+                _ => Flow::Branch(target),
+            }
+        };
+        match self.command {
+            Command::JIT => jumps_if(true),
+            Command::JIF => jumps_if(false),
+            Command::STOP => Flow::Halt,
+            // The handler decides at runtime whether to jump or halt.
+            Command::CUSTOM(_) => Flow::Branch(Target::Computed),
+            _ => Flow::Next,
+        }
+    }
+
+    pub(crate) fn command(&self) -> Command {
+        self.command
+    }
+
+    /// The value written by an ADD or MULTIPLY whose inputs are both immediate, unless
+    /// it overflows.
+    pub fn constant_result(&self) -> Option<i128> {
+        match (&self.command, &self.operands[..]) {
+            (Command::ADD, [Operand::Immediate(a), Operand::Immediate(b), _]) => {
+                i128::checked_add(*a, *b)
+            }
+            (Command::MULTIPLY, [Operand::Immediate(a), Operand::Immediate(b), _]) => {
+                i128::checked_mul(*a, *b)
+            }
+            _ => None,
+        }
+    }
+
+    /// Addresses execution can continue at that are known without running the program.
+    pub fn successors(&self) -> Vec<usize> {
+        let next = self.address + self.len();
+        match self.flow() {
+            Flow::Next => vec![next],
+            Flow::Jump(Target::Known(target)) => vec![target],
+            Flow::Branch(Target::Known(target)) => vec![next, target],
+            Flow::Branch(Target::Computed) => vec![next],
+            Flow::Jump(Target::Computed) | Flow::Halt => vec![],
+        }
+    }
+}
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operands = self
+            .operands
+            .iter()
+            .map(|operand| operand.to_string())
+            .collect::<Vec<String>>();
+        if operands.is_empty() {
+            write!(f, "{}", self.mnemonic())
+        } else {
+            write!(f, "{} {}", self.mnemonic(), operands.join(", "))
+        }
+    }
+}
+
+struct Line {
+    address: usize,
+    end: usize,
+    text: String,
+    is_label: bool,
+}
+
+/// A static listing of a program. Code is found by following control flow from the entry
+/// points; every word that is not part of a reachable instruction is treated as data.
+#[derive(Debug, Clone)]
+pub struct Disassembly {
+    words: Vec<i128>,
+    instructions: BTreeMap<usize, Instruction>,
+    jump_targets: BTreeSet<usize>,
+}
+impl Disassembly {
+    pub fn new(words: Vec<i128>) -> Self {
+        Disassembly::with_entry_points(words, &[0])
+    }
+
+    /// Disassembles from extra entry points as well, for code only reached through
+    /// computed jumps.
+    pub fn with_entry_points(words: Vec<i128>, entry_points: &[usize]) -> Self {
+        let mut instructions = BTreeMap::new();
+        let mut jump_targets = BTreeSet::new();
+        let mut pending = entry_points.to_vec();
+        while let Some(address) = pending.pop() {
+            if instructions.contains_key(&address) {
+                continue;
+            }
+            let instruction = match Instruction::decode(&words, address) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            if let Flow::Jump(Target::Known(target)) | Flow::Branch(Target::Known(target)) =
+                instruction.flow()
+            {
+                jump_targets.insert(target);
+            }
+            pending.extend(instruction.successors());
+            pending.extend(return_site(&words, &instruction));
+            instructions.insert(address, instruction);
+        }
+        Disassembly {
+            words,
+            instructions,
+            jump_targets,
+        }
+    }
+
+    pub fn words(&self) -> &[i128] {
+        &self.words
+    }
+
+    /// Reachable instructions in address order.
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.instructions.values()
+    }
+
+    pub fn instruction_at(&self, address: usize) -> Option<&Instruction> {
+        self.instructions.get(&address)
+    }
+
+    pub fn is_jump_target(&self, address: usize) -> bool {
+        self.jump_targets.contains(&address)
+    }
+
+    /// Whether the word at `address` belongs to a reachable instruction.
+    pub fn is_code(&self, address: usize) -> bool {
+        match self.instructions.range(..=address).next_back() {
+            Some((start, instruction)) => address < start + instruction.len(),
+            None => false,
+        }
+    }
+
+    /// Assembler source for the program: the listing without addresses or raw words.
+    /// Assembling it gives back the original program.
+    pub fn source(&self) -> String {
+        let mut source = String::new();
+        for line in self.lines() {
+            if line.is_label {
+                source.push_str(&format!("L{}:\n", line.address));
+            }
+            source.push_str(&format!("    {}\n", line.text));
+        }
+        source
+    }
+
+    fn lines(&self) -> Vec<Line> {
+        let mut lines = vec![];
+        let mut address = 0;
+        while address < self.words.len() {
+            if let Some(instruction) = self.instructions.get(&address) {
+                lines.push(Line {
+                    address,
+                    end: address + instruction.len(),
+                    text: instruction.to_string(),
+                    is_label: self.is_jump_target(address),
+                });
+                address += instruction.len();
+                continue;
+            }
+
+            let mut end = address + 1;
+            while end < self.words.len()
+                && end - address < DATA_WORDS_PER_LINE
+                && !self.instructions.contains_key(&end)
+            {
+                end += 1;
+            }
+            let values = self.words[address..end]
+                .iter()
+                .map(|word| word.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            lines.push(Line {
+                address,
+                end,
+                text: format!("db {}", values),
+                is_label: false,
+            });
+            address = end;
+        }
+        lines
+    }
+}
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.lines() {
+            if line.is_label {
+                writeln!(f, "L{}:", line.address)?;
+            }
+            let words = self.words[line.address..line.end]
+                .iter()
+                .map(|word| word.to_string())
+                .collect::<Vec<String>>()
+                .join(" ");
+            writeln!(
+                f,
+                "{:>6}: {:<width$} {}",
+                line.address,
+                words,
+                line.text,
+                width = WORDS_COLUMN_WIDTH
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Recognizes the usual call sequence, a constant return address stored just before an
+/// unconditional jump, and returns the address the callee will come back to. The jump
+/// may be through a function pointer.
+pub(crate) fn return_site(words: &[i128], instruction: &Instruction) -> Option<usize> {
+    let return_address = instruction.constant_result()?;
+    let jump = Instruction::decode(words, instruction.address + instruction.len())?;
+    let return_address = usize::try_from(return_address).ok()?;
+    match jump.flow() {
+        Flow::Jump(_) if return_address == jump.address + jump.len() => Some(return_address),
+        _ => None,
+    }
+}
+
+pub fn disassemble(program_string: &str) -> Result<Disassembly, IntcodeError> {
+    Ok(Disassembly::new(parse_program(program_string)?))
+}
@@ -0,0 +1,115 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::{Access, Limit, Violation};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    /// A value in the program text is not an integer; `index` counts values from 0.
+    Parse { index: usize, value: String },
+    /// Assembler source could not be assembled; `line` is 1-based.
+    Assembly { line: usize, message: String },
+    /// A line of a saved trace could not be read; `line` is 1-based.
+    Trace { line: usize, message: String },
+    /// A program file could not be read or written.
+    File { path: String, message: String },
+    /// Binary program data is malformed at byte `position`, or a cell at index
+    /// `position` can't be encoded.
+    Binary { position: usize, message: String },
+    /// The opcode at `ip` does not name a known command.
+    InvalidOpcode { ip: usize, opcode: i128 },
+    /// The opcode at `ip` uses a parameter mode other than position, immediate or relative.
+    InvalidMode { ip: usize, opcode: i128, mode: u32 },
+    /// An input instruction ran with nothing left to read.
+    MissingInput { ip: usize, opcode: i128 },
+    /// A custom opcode's handler failed.
+    Extension {
+        ip: usize,
+        opcode: i128,
+        message: String,
+    },
+    /// An opcode could not be added to an `OpcodeRegistry`.
+    Register { opcode: i128, message: String },
+    /// An ADD or MULTIPLY result, or an address, did not fit in its type.
+    Overflow { ip: usize, opcode: i128 },
+    /// The program went past one of the `Limits` set with `Program::with_limits`.
+    LimitExceeded { ip: usize, limit: Limit },
+    /// An access trapped by the program's `AccessPolicy`.
+    AccessViolation {
+        ip: usize,
+        address: usize,
+        access: Access,
+    },
+    /// A read, write or jump resolved to an address below zero.
+    NegativeAddress {
+        ip: usize,
+        opcode: i128,
+        address: i128,
+    },
+}
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntcodeError::Parse { index, value } => {
+                write!(f, "Invalid value {:?} at position {}", value, index)
+            }
+            IntcodeError::Assembly { line, message } => write!(f, "Line {}: {}", line, message),
+            IntcodeError::Trace { line, message } => {
+                write!(f, "Trace line {}: {}", line, message)
+            }
+            IntcodeError::File { path, message } => write!(f, "{}: {}", path, message),
+            IntcodeError::Binary { position, message } => {
+                write!(f, "Binary data at {}: {}", position, message)
+            }
+            IntcodeError::InvalidOpcode { ip, opcode } => {
+                write!(f, "{}: Invalid opcode {}", ip, opcode)
+            }
+            IntcodeError::InvalidMode { ip, opcode, mode } => {
+                write!(f, "{}: Invalid mode {} for opcode {}", ip, mode, opcode)
+            }
+            IntcodeError::MissingInput { ip, opcode } => {
+                write!(
+                    f,
+                    "{}: Opcode {} needs an input but none was sent",
+                    ip, opcode
+                )
+            }
+            IntcodeError::Extension {
+                ip,
+                opcode,
+                message,
+            } => write!(f, "{}: Opcode {} failed: {}", ip, opcode, message),
+            IntcodeError::Register { opcode, message } => {
+                write!(f, "Cannot register opcode {}: {}", opcode, message)
+            }
+            IntcodeError::Overflow { ip, opcode } => {
+                write!(f, "{}: Opcode {} overflowed", ip, opcode)
+            }
+            IntcodeError::LimitExceeded { ip, limit } => write!(f, "{}: {}", ip, limit),
+            IntcodeError::AccessViolation {
+                ip,
+                address,
+                access,
+            } => write!(f, "{}: {} at {}", ip, access, address),
+            IntcodeError::NegativeAddress {
+                ip,
+                opcode,
+                address,
+            } => write!(
+                f,
+                "{}: Opcode {} used negative address {}",
+                ip, opcode, address
+            ),
+        }
+    }
+}
+impl Error for IntcodeError {}
+impl From<Violation> for IntcodeError {
+    fn from(violation: Violation) -> Self {
+        IntcodeError::AccessViolation {
+            ip: violation.ip,
+            address: violation.address,
+            access: violation.access,
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{InputSource, OutputSink};
+
+/// Instructions `Program::run_async` executes before giving other tasks a turn.
+pub(crate) const YIELD_STEPS: usize = 1024;
+
+/// An input source a program can wait on without blocking its thread.
+pub trait AsyncInput<C = i128>: InputSource<C> {
+    /// `Ready(true)` once `next_input` has a value, `Ready(false)` if one will never
+    /// come. Otherwise wakes the task when a value arrives.
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<bool>;
+}
+
+/// Nothing else can add to a program's own queue while it waits, so an empty queue stays empty.
+impl<C> AsyncInput<C> for VecDeque<C> {
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<bool> {
+        Poll::Ready(!self.is_empty())
+    }
+}
+
+#[derive(Debug)]
+struct ChannelState<C> {
+    queue: VecDeque<C>,
+    idle_input: Option<C>,
+    polls: usize,
+    waker: Option<Waker>,
+}
+
+/// A queue shared between its clones that wakes the program waiting on it, for wiring
+/// programs together on an `Executor`. Unlike `Pipe` it stays on one thread.
+#[derive(Debug)]
+pub struct Channel<C = i128> {
+    state: Rc<RefCell<ChannelState<C>>>,
+}
+impl<C> Clone for Channel<C> {
+    fn clone(&self) -> Self {
+        Channel {
+            state: Rc::clone(&self.state),
+        }
+    }
+}
+impl<C> Default for Channel<C> {
+    fn default() -> Self {
+        Channel {
+            state: Rc::new(RefCell::new(ChannelState {
+                queue: VecDeque::new(),
+                idle_input: None,
+                polls: 0,
+                waker: None,
+            })),
+        }
+    }
+}
+impl<C> Channel<C> {
+    pub fn new() -> Self {
+        Channel::default()
+    }
+
+    /// Reads from an empty channel get `value` instead of waiting, like the -1 of a
+    /// non-blocking network card.
+    pub fn with_idle_input(self, value: C) -> Self {
+        self.state.borrow_mut().idle_input = Some(value);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.borrow().queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.borrow().queue.is_empty()
+    }
+
+    /// Reads in a row that found the channel empty and got the idle input.
+    pub fn polls(&self) -> usize {
+        self.state.borrow().polls
+    }
+
+    pub fn send(&self, value: C) {
+        let mut state = self.state.borrow_mut();
+        state.queue.push_back(value);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+impl<C: Clone> InputSource<C> for Channel<C> {
+    fn next_input(&mut self) -> Option<C> {
+        let mut state = self.state.borrow_mut();
+        if let Some(value) = state.queue.pop_front() {
+            state.polls = 0;
+            return Some(value);
+        }
+        let value = state.idle_input.clone()?;
+        state.polls += 1;
+        Some(value)
+    }
+}
+impl<C: Clone> AsyncInput<C> for Channel<C> {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<bool> {
+        let mut state = self.state.borrow_mut();
+        if state.queue.is_empty() && state.idle_input.is_none() {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(true)
+        }
+    }
+}
+impl<C> OutputSink<C> for Channel<C> {
+    fn send_output(&mut self, value: C) {
+        self.send(value);
+    }
+}
+
+thread_local! {
+    /// Wakers of `Sleep`s on this thread, with when to wake them.
+    static TIMERS: RefCell<Vec<(Instant, Waker)>> = const { RefCell::new(vec![]) };
+}
+
+/// Finishes once `duration` has passed, while an `Executor` runs other tasks.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        deadline: Instant::now() + duration,
+    }
+}
+
+#[derive(Debug)]
+pub struct Sleep {
+    deadline: Instant,
+}
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        let timer = (self.deadline, cx.waker().clone());
+        TIMERS.with(|timers| timers.borrow_mut().push(timer));
+        Poll::Pending
+    }
+}
+
+/// Lets every other ready task run once before continuing.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+#[derive(Debug)]
+pub struct YieldNow {
+    yielded: bool,
+}
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Index the future passed to `Executor::run_until` is woken under.
+const MAIN: usize = usize::MAX;
+
+struct TaskWaker {
+    index: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.index);
+    }
+}
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// Runs tasks on the current thread, polling each one again only once it's woken, so
+/// programs waiting on a `Channel` cost nothing until someone sends to them.
+#[derive(Default)]
+pub struct Executor {
+    tasks: Vec<Option<Task>>,
+    remaining: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+impl Executor {
+    pub fn new() -> Self {
+        Executor::default()
+    }
+
+    pub fn spawn<F: Future<Output = ()> + 'static>(&mut self, task: F) {
+        self.ready.lock().unwrap().push_back(self.tasks.len());
+        self.tasks.push(Some(Box::pin(task)));
+        self.remaining += 1;
+    }
+
+    /// Runs until every spawned task has finished. Returns false if the remaining tasks
+    /// are all waiting on something that will never happen.
+    pub fn run(&mut self) -> bool {
+        while self.remaining > 0 {
+            match self.next_ready() {
+                Some(MAIN) => {}
+                Some(index) => self.poll_task(index),
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Runs the spawned tasks alongside `main` until `main` finishes, and returns its
+    /// output. Returns `None` if everything gets stuck first.
+    pub fn run_until<F: Future>(&mut self, main: F) -> Option<F::Output> {
+        let mut main = Box::pin(main);
+        let waker = self.waker(MAIN);
+        self.ready.lock().unwrap().push_back(MAIN);
+        while let Some(index) = self.next_ready() {
+            if index != MAIN {
+                self.poll_task(index);
+            } else if let Poll::Ready(output) = main.as_mut().poll(&mut Context::from_waker(&waker))
+            {
+                return Some(output);
+            }
+        }
+        None
+    }
+
+    fn waker(&self, index: usize) -> Waker {
+        Waker::from(Arc::new(TaskWaker {
+            index,
+            ready: self.ready.clone(),
+        }))
+    }
+
+    fn poll_task(&mut self, index: usize) {
+        let waker = self.waker(index);
+        if let Some(task) = &mut self.tasks[index] {
+            if task
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_ready()
+            {
+                self.tasks[index] = None;
+                self.remaining -= 1;
+            }
+        }
+    }
+
+    /// The next woken task, sleeping until the earliest timer if none is woken.
+    fn next_ready(&mut self) -> Option<usize> {
+        loop {
+            let now = Instant::now();
+            wake_timers(now);
+            if let Some(index) = self.ready.lock().unwrap().pop_front() {
+                return Some(index);
+            }
+            let earliest = TIMERS
+                .with(|timers| timers.borrow().iter().map(|&(deadline, _)| deadline).min())?;
+            if earliest > now {
+                thread::sleep(earliest - now);
+            }
+        }
+    }
+}
+
+fn wake_timers(now: Instant) {
+    let due: Vec<Waker> = TIMERS.with(|timers| {
+        let mut timers = timers.borrow_mut();
+        let mut due = vec![];
+        timers.retain(|(deadline, waker)| {
+            if *deadline <= now {
+                due.push(waker.clone());
+            }
+            *deadline > now
+        });
+        due
+    });
+    for waker in due {
+        waker.wake();
+    }
+}
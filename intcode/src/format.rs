@@ -0,0 +1,242 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use crate::{Cell, IntcodeError};
+
+// Binary files start with `MAGIC`, then the number of cells and each cell, all as
+// LEB128 varints. Cells are zigzag-encoded first so small negatives stay short:
+// 0, -1, 1, -2, 2, ... become 0, 1, 2, 3, 4, ...
+//
+// Saved VM states start with `STATE_MAGIC`, then the ip, the relative base (zigzag),
+// and the pending inputs and the memory, each as a count followed by cells. Last come
+// the cells kept sparsely past the memory, as a count followed by address and cell
+// pairs.
+
+const MAGIC: &[u8] = b"INTC\x01";
+const STATE_MAGIC: &[u8] = b"INTS\x02";
+
+/// Everything needed to resume a program with its own input queue.
+pub(crate) struct SavedState<C> {
+    pub ip: usize,
+    pub relative_base: i128,
+    pub inputs: Vec<C>,
+    pub memory: Vec<C>,
+    pub sparse: Vec<(usize, C)>,
+}
+
+/// Parses program text that may span lines: values are separated by commas and/or
+/// whitespace, and `#` starts a comment that runs to the end of the line.
+pub fn parse_text<C: Cell>(text: &str) -> Result<Vec<C>, IntcodeError> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|value| !value.is_empty())
+        .enumerate()
+        .map(|(index, value)| {
+            value.parse().map_err(|_| IntcodeError::Parse {
+                index,
+                value: String::from(value),
+            })
+        })
+        .collect()
+}
+
+/// Serializes cells in the compact binary format. Fails for a `BigInt` cell that
+/// doesn't fit in an i128.
+pub fn encode_binary<C: Cell>(cells: &[C]) -> Result<Vec<u8>, IntcodeError> {
+    let mut bytes = MAGIC.to_vec();
+    write_cells(&mut bytes, cells)?;
+    Ok(bytes)
+}
+
+/// Reads cells written by `encode_binary`.
+pub fn decode_binary<C: Cell>(bytes: &[u8]) -> Result<Vec<C>, IntcodeError> {
+    let mut position = check_magic(bytes, MAGIC, "not an intcode binary")?;
+    let cells = read_cells(bytes, &mut position)?;
+    check_end(bytes, position)?;
+    Ok(cells)
+}
+
+fn encode_state<C: Cell>(state: &SavedState<C>) -> Result<Vec<u8>, IntcodeError> {
+    let mut bytes = STATE_MAGIC.to_vec();
+    write_varint(&mut bytes, state.ip as u128);
+    write_varint(&mut bytes, zigzag(state.relative_base));
+    write_cells(&mut bytes, &state.inputs)?;
+    write_cells(&mut bytes, &state.memory)?;
+    write_varint(&mut bytes, state.sparse.len() as u128);
+    for (address, cell) in state.sparse.iter() {
+        write_varint(&mut bytes, *address as u128);
+        write_cell(&mut bytes, *address, cell)?;
+    }
+    Ok(bytes)
+}
+
+fn decode_state<C: Cell>(bytes: &[u8]) -> Result<SavedState<C>, IntcodeError> {
+    let mut position = check_magic(bytes, STATE_MAGIC, "not a saved intcode state")?;
+    let ip = read_address(bytes, &mut position, "ip is out of range")?;
+    let relative_base = unzigzag(read_varint(bytes, &mut position)?);
+    let inputs = read_cells(bytes, &mut position)?;
+    let memory = read_cells(bytes, &mut position)?;
+    let mut sparse = vec![];
+    for _ in 0..read_varint(bytes, &mut position)? {
+        let address = read_address(bytes, &mut position, "address is out of range")?;
+        sparse.push((address, read_cell(bytes, &mut position)?));
+    }
+    check_end(bytes, position)?;
+    Ok(SavedState {
+        ip,
+        relative_base,
+        inputs,
+        memory,
+        sparse,
+    })
+}
+
+/// Reads a program from a file, either binary or text as `parse_text` accepts.
+pub fn read_program<C: Cell>(path: impl AsRef<Path>) -> Result<Vec<C>, IntcodeError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|e| file_error(path, e))?;
+    if bytes.starts_with(MAGIC) {
+        return decode_binary(&bytes);
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => parse_text(&text),
+        Err(_) => Err(IntcodeError::File {
+            path: path.display().to_string(),
+            message: String::from("neither text nor an intcode binary"),
+        }),
+    }
+}
+
+/// Writes cells to a file in the binary format.
+pub fn write_binary<C: Cell>(path: impl AsRef<Path>, cells: &[C]) -> Result<(), IntcodeError> {
+    let path = path.as_ref();
+    fs::write(path, encode_binary(cells)?).map_err(|e| file_error(path, e))
+}
+
+pub(crate) fn write_state<C: Cell>(
+    path: impl AsRef<Path>,
+    state: &SavedState<C>,
+) -> Result<(), IntcodeError> {
+    let path = path.as_ref();
+    fs::write(path, encode_state(state)?).map_err(|e| file_error(path, e))
+}
+
+pub(crate) fn read_state<C: Cell>(path: impl AsRef<Path>) -> Result<SavedState<C>, IntcodeError> {
+    let path = path.as_ref();
+    decode_state(&fs::read(path).map_err(|e| file_error(path, e))?)
+}
+
+fn file_error(path: &Path, error: std::io::Error) -> IntcodeError {
+    IntcodeError::File {
+        path: path.display().to_string(),
+        message: error.to_string(),
+    }
+}
+
+fn check_magic(bytes: &[u8], magic: &[u8], message: &str) -> Result<usize, IntcodeError> {
+    if bytes.starts_with(magic) {
+        Ok(magic.len())
+    } else {
+        Err(IntcodeError::Binary {
+            position: 0,
+            message: String::from(message),
+        })
+    }
+}
+
+fn check_end(bytes: &[u8], position: usize) -> Result<(), IntcodeError> {
+    if position == bytes.len() {
+        Ok(())
+    } else {
+        Err(IntcodeError::Binary {
+            position,
+            message: String::from("trailing bytes"),
+        })
+    }
+}
+
+fn write_cells<C: Cell>(bytes: &mut Vec<u8>, cells: &[C]) -> Result<(), IntcodeError> {
+    write_varint(bytes, cells.len() as u128);
+    for (position, cell) in cells.iter().enumerate() {
+        write_cell(bytes, position, cell)?;
+    }
+    Ok(())
+}
+
+/// Writes `cell`, the one at index `position`, for errors to point at.
+fn write_cell<C: Cell>(bytes: &mut Vec<u8>, position: usize, cell: &C) -> Result<(), IntcodeError> {
+    let value = cell.to_i128().ok_or_else(|| IntcodeError::Binary {
+        position,
+        message: String::from("value does not fit in 128 bits"),
+    })?;
+    write_varint(bytes, zigzag(value));
+    Ok(())
+}
+
+fn read_cells<C: Cell>(bytes: &[u8], position: &mut usize) -> Result<Vec<C>, IntcodeError> {
+    let count = read_varint(bytes, position)?;
+    let mut cells = vec![];
+    for _ in 0..count {
+        cells.push(read_cell(bytes, position)?);
+    }
+    Ok(cells)
+}
+
+fn read_cell<C: Cell>(bytes: &[u8], position: &mut usize) -> Result<C, IntcodeError> {
+    let start = *position;
+    let value = unzigzag(read_varint(bytes, position)?);
+    C::from_i128(value).ok_or_else(|| IntcodeError::Binary {
+        position: start,
+        message: format!("{} does not fit in a cell", value),
+    })
+}
+
+fn read_address(bytes: &[u8], position: &mut usize, message: &str) -> Result<usize, IntcodeError> {
+    let start = *position;
+    usize::try_from(read_varint(bytes, position)?).map_err(|_| IntcodeError::Binary {
+        position: start,
+        message: String::from(message),
+    })
+}
+
+fn zigzag(value: i128) -> u128 {
+    ((value << 1) ^ (value >> 127)) as u128
+}
+
+fn unzigzag(value: u128) -> i128 {
+    (value >> 1) as i128 ^ -((value & 1) as i128)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> Result<u128, IntcodeError> {
+    let start = *position;
+    let mut value = 0u128;
+    for shift in (0..128).step_by(7) {
+        let byte = *bytes.get(*position).ok_or_else(|| IntcodeError::Binary {
+            position: start,
+            message: String::from("unexpected end of data"),
+        })?;
+        *position += 1;
+        let bits = u128::from(byte & 0x7f);
+        if shift > 0 && bits >> (128 - shift) != 0 {
+            break;
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(IntcodeError::Binary {
+        position: start,
+        message: String::from("varint is too long"),
+    })
+}
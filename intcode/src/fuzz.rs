@@ -0,0 +1,621 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{
+    assemble_words, decompile, AccessPolicy, Command, ControlFlowGraph, Disassembly, HashMemory,
+    IntcodeError, Limit, Limits, Memory, PagedMemory, Policy, Program, StepResult, SymbolicProgram,
+    VecMemory,
+};
+
+const CASES: usize = 256;
+const MAX_LEN: usize = 24;
+const DATA_LEN: usize = 8;
+const MAX_STEPS: u64 = 10_000;
+const MAX_MEMORY: usize = 4096;
+/// Candidates to try before settling for the smallest failing case found so far.
+const MAX_SHRINKS: usize = 10_000;
+
+/// Commands to generate, the common ones repeated to make them likelier.
+const COMMANDS: [Command; 16] = [
+    Command::ADD,
+    Command::ADD,
+    Command::MULTIPLY,
+    Command::MULTIPLY,
+    Command::INPUT,
+    Command::OUTPUT,
+    Command::OUTPUT,
+    Command::JIT,
+    Command::JIF,
+    Command::LESS,
+    Command::EQUALS,
+    Command::LESS,
+    Command::EQUALS,
+    Command::REL,
+    Command::REL,
+    Command::STOP,
+];
+
+/// Values that make arithmetic and addressing overflow.
+const EXTREMES: [i128; 6] = [
+    i128::MAX,
+    i128::MIN,
+    i64::MAX as i128,
+    usize::MAX as i128,
+    -1,
+    1 << 100,
+];
+
+/// xorshift64*, seeded through splitmix64 so that nearby seeds give unrelated streams.
+struct Rng(u64);
+impl Rng {
+    fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        Rng((z ^ (z >> 31)) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn range(&mut self, low: i128, high: i128) -> i128 {
+        low + (self.next() % (high - low + 1) as u64) as i128
+    }
+
+    fn small(&mut self) -> i128 {
+        self.range(-4, 9)
+    }
+
+    /// An address so close to `usize::MAX` that an instruction there runs off the end.
+    fn far(&mut self) -> i128 {
+        usize::MAX as i128 - self.range(0, 3)
+    }
+
+    fn value(&mut self) -> i128 {
+        match self.below(16) {
+            0 => EXTREMES[self.below(EXTREMES.len())],
+            _ => self.small(),
+        }
+    }
+}
+
+/// Values closer to zero to try in place of `value`.
+fn smaller(value: i128) -> Vec<i128> {
+    let mut smaller = vec![0, value / 2];
+    smaller.dedup();
+    smaller.retain(|&smaller| smaller != value);
+    smaller
+}
+
+/// An operand of a generated instruction. Jump targets and data cells are named
+/// rather than given as addresses, so instructions can be removed without breaking
+/// the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Arg {
+    Immediate(i128),
+    /// Position mode, naming a cell of the data block.
+    Cell(usize),
+    /// Position mode at a fixed address, for writes far past the program.
+    Address(i128),
+    Relative(i128),
+    /// The address of the instruction with this index, as an immediate.
+    Label(usize),
+}
+impl Arg {
+    fn simpler(&self) -> Vec<Arg> {
+        match self {
+            Arg::Immediate(value) => smaller(*value).into_iter().map(Arg::Immediate).collect(),
+            Arg::Relative(offset) => smaller(*offset).into_iter().map(Arg::Relative).collect(),
+            Arg::Cell(index) if *index > 0 => vec![Arg::Cell(0)],
+            Arg::Address(_) => vec![Arg::Cell(0)],
+            Arg::Cell(_) | Arg::Label(_) => vec![],
+        }
+    }
+}
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arg::Immediate(value) => write!(f, "#{}", value),
+            Arg::Cell(index) => write!(f, "[data+{}]", index),
+            Arg::Address(address) => write!(f, "[{}]", address),
+            Arg::Relative(offset) if *offset < 0 => write!(f, "[rb-{}]", offset.unsigned_abs()),
+            Arg::Relative(offset) => write!(f, "[rb+{}]", offset),
+            Arg::Label(index) => write!(f, "#l{}", index),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Op {
+    command: Command,
+    args: Vec<Arg>,
+}
+
+/// A generated program and the inputs to run it with. Displays as assembler source,
+/// every instruction labelled `l<index>`, followed by a HALT and a data block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzCase {
+    ops: Vec<Op>,
+    data: Vec<i128>,
+    inputs: Vec<i128>,
+}
+impl FuzzCase {
+    fn generate(rng: &mut Rng, max_len: usize) -> Self {
+        let len = rng.below(max_len) + 1;
+        let data = (0..DATA_LEN).map(|_| rng.value()).collect();
+        let inputs = (0..rng.below(5)).map(|_| rng.value()).collect();
+        let ops = (0..len)
+            .map(|_| {
+                let command = COMMANDS[rng.below(COMMANDS.len())];
+                let args = (0..command.num_params())
+                    .map(|offset| {
+                        let jump = matches!(command, Command::JIT | Command::JIF) && offset == 1;
+                        match rng.below(8) {
+                            0..=4 if jump => Arg::Label(rng.below(len + 1)),
+                            5 if jump => Arg::Immediate(rng.far()),
+                            _ if command.is_write(offset) => match rng.below(8) {
+                                0..=1 => Arg::Relative(rng.range(-1, 9)),
+                                2 => Arg::Address(rng.far()),
+                                _ => Arg::Cell(rng.below(DATA_LEN)),
+                            },
+                            0..=1 if command == Command::REL => Arg::Immediate(rng.range(-2, 4)),
+                            2 if command == Command::REL => Arg::Immediate(rng.far()),
+                            0..=2 => Arg::Immediate(rng.value()),
+                            3..=5 => Arg::Cell(rng.below(DATA_LEN)),
+                            _ => Arg::Relative(rng.range(-1, 9)),
+                        }
+                    })
+                    .collect();
+                Op { command, args }
+            })
+            .collect();
+        FuzzCase { ops, data, inputs }
+    }
+
+    pub fn inputs(&self) -> &[i128] {
+        &self.inputs
+    }
+
+    pub fn words(&self) -> Vec<i128> {
+        assemble_words(&self.to_string()).expect("generated source should assemble")
+    }
+
+    /// Every case one step simpler than this one: with an instruction or input left
+    /// out, or with an operand or value closer to zero.
+    fn simpler(&self) -> Vec<FuzzCase> {
+        let mut simpler = vec![];
+        for index in (0..self.ops.len()).rev() {
+            let mut case = self.clone();
+            case.ops.remove(index);
+            for arg in case.ops.iter_mut().flat_map(|op| op.args.iter_mut()) {
+                match arg {
+                    Arg::Label(target) if *target > index => *target -= 1,
+                    _ => {}
+                }
+            }
+            simpler.push(case);
+        }
+        for index in (0..self.inputs.len()).rev() {
+            let mut case = self.clone();
+            case.inputs.remove(index);
+            simpler.push(case);
+        }
+        for (index, op) in self.ops.iter().enumerate() {
+            for (offset, arg) in op.args.iter().enumerate() {
+                for arg in arg.simpler() {
+                    let mut case = self.clone();
+                    case.ops[index].args[offset] = arg;
+                    simpler.push(case);
+                }
+            }
+        }
+        let values = |values: &[i128]| {
+            let mut simpler = vec![];
+            for (index, &value) in values.iter().enumerate() {
+                for smaller in smaller(value) {
+                    let mut values = values.to_vec();
+                    values[index] = smaller;
+                    simpler.push(values);
+                }
+            }
+            simpler
+        };
+        for data in values(&self.data) {
+            simpler.push(FuzzCase {
+                data,
+                ..self.clone()
+            });
+        }
+        for inputs in values(&self.inputs) {
+            simpler.push(FuzzCase {
+                inputs,
+                ..self.clone()
+            });
+        }
+        simpler
+    }
+}
+impl fmt::Display for FuzzCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |values: &[i128]| {
+            values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        let inputs = format!("; inputs: {}", join(&self.inputs));
+        writeln!(f, "{}", inputs.trim_end())?;
+        for (index, op) in self.ops.iter().enumerate() {
+            let args = op
+                .args
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            writeln!(f, "l{}: {} {}", index, op.command.mnemonic(), args)?;
+        }
+        writeln!(f, "l{}: HALT", self.ops.len())?;
+        writeln!(f, "data: db {}", join(&self.data))
+    }
+}
+
+/// An invariant the fuzzer checks every case against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Property {
+    /// Nothing panics, whatever the program does.
+    NoPanic,
+    /// Running the same case twice ends the same way.
+    Deterministic,
+    /// A program restored from a snapshot taken halfway carries on exactly as the
+    /// original did.
+    SnapshotRestore,
+    /// `VecMemory`, `HashMemory` and `PagedMemory` behave the same.
+    MemoryBackends,
+    /// Nothing panics with no memory limit, whether running under an access policy,
+    /// running symbolically, or disassembling, graphing and decompiling the program.
+    Unbounded,
+    /// One added with `Fuzzer::property`.
+    Custom(String),
+}
+impl fmt::Display for Property {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Property::NoPanic => write!(f, "no panics"),
+            Property::Deterministic => write!(f, "determinism"),
+            Property::SnapshotRestore => write!(f, "snapshot and restore"),
+            Property::MemoryBackends => write!(f, "memory backend equivalence"),
+            Property::Unbounded => write!(f, "unbounded memory"),
+            Property::Custom(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A case that broke a property, and the smallest case shrinking found that still
+/// breaks it.
+#[derive(Debug, Clone)]
+pub struct FuzzFailure {
+    /// Passing this to `Fuzzer::generate` gives `original` back.
+    pub seed: u64,
+    pub property: Property,
+    pub message: String,
+    pub original: FuzzCase,
+    pub shrunk: FuzzCase,
+}
+impl fmt::Display for FuzzFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Case {} breaks {}: {}",
+            self.seed, self.property, self.message
+        )?;
+        write!(f, "{}", self.shrunk)
+    }
+}
+
+/// How a run ended and the state it left behind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Run {
+    outputs: Vec<i128>,
+    end: Result<StepResult, IntcodeError>,
+    /// Memory with trailing zeros trimmed, which backends may or may not store.
+    memory: Vec<i128>,
+    /// Nonzero cells past the contiguous ones.
+    far: Vec<(usize, i128)>,
+    ip: usize,
+    relative_base: i128,
+}
+impl Run {
+    fn compare(&self, other: &Run) -> Result<(), String> {
+        if self.end != other.end {
+            Err(format!("ended with {:?}, then {:?}", self.end, other.end))
+        } else if self.outputs != other.outputs {
+            Err(format!(
+                "output {:?}, then {:?}",
+                self.outputs, other.outputs
+            ))
+        } else if (self.ip, self.relative_base) != (other.ip, other.relative_base) {
+            Err(format!(
+                "stopped at ip {} with relative base {}, then at ip {} with {}",
+                self.ip, self.relative_base, other.ip, other.relative_base
+            ))
+        } else if self.memory != other.memory {
+            let address = (0..)
+                .find(|&address| self.memory.get(address) != other.memory.get(address))
+                .unwrap();
+            Err(format!(
+                "left {:?} at {}, then {:?}",
+                self.memory.get(address),
+                address,
+                other.memory.get(address)
+            ))
+        } else if self.far != other.far {
+            Err(format!(
+                "left far cells {:?}, then {:?}",
+                self.far, other.far
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Runs `program` until it halts, blocks on input, fails or hits its limits.
+fn finish<M: Memory<Cell = i128>>(program: &mut FuzzProgram<M>, mut outputs: Vec<i128>) -> Run {
+    let end = loop {
+        match program.execute() {
+            Ok(StepResult::Continued) => {}
+            Ok(StepResult::Output(value)) => outputs.push(value),
+            Ok(step) => break Ok(step),
+            Err(e) => break Err(e),
+        }
+    };
+    let mut memory = program.memory().to_vec();
+    while memory.last() == Some(&0) {
+        memory.pop();
+    }
+    Run {
+        outputs,
+        end,
+        memory,
+        far: program.memory().sparse_cells(),
+        ip: program.ip(),
+        relative_base: program.relative_base(),
+    }
+}
+
+type FuzzProgram<M> = Program<VecDeque<i128>, (), M>;
+
+type Check = Box<dyn Fn(&FuzzCase) -> Result<(), String>>;
+
+/// Generates random well-formed programs, runs them under limits and checks that they
+/// keep to a set of `Property`s, shrinking any case that doesn't.
+pub struct Fuzzer {
+    seed: u64,
+    cases: usize,
+    max_len: usize,
+    limits: Limits,
+    properties: Vec<(String, Check)>,
+}
+impl Fuzzer {
+    pub fn new() -> Self {
+        Fuzzer {
+            seed: 0,
+            cases: CASES,
+            max_len: MAX_LEN,
+            limits: Limits::new().steps(MAX_STEPS).memory(MAX_MEMORY),
+            properties: vec![],
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn cases(mut self, cases: usize) -> Self {
+        self.cases = cases;
+        self
+    }
+
+    /// Instructions per program at most, not counting the final HALT.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len.max(1);
+        self
+    }
+
+    /// Steps each run may take. Writes are kept below a small address, except when
+    /// checking `Property::Unbounded`.
+    pub fn steps(mut self, max_steps: u64) -> Self {
+        self.limits = self.limits.steps(max_steps);
+        self
+    }
+
+    /// Checks every case against `check` as well, after the built-in properties.
+    pub fn property<F>(mut self, name: &str, check: F) -> Self
+    where
+        F: Fn(&FuzzCase) -> Result<(), String> + 'static,
+    {
+        self.properties.push((name.to_string(), Box::new(check)));
+        self
+    }
+
+    pub fn generate(&self, seed: u64) -> FuzzCase {
+        FuzzCase::generate(&mut Rng::new(seed), self.max_len)
+    }
+
+    /// Checks `cases` cases, seeded one after the other from `seed`, and returns how
+    /// many passed.
+    pub fn run(&self) -> Result<usize, Box<FuzzFailure>> {
+        for index in 0..self.cases {
+            let seed = self.seed.wrapping_add(index as u64);
+            let case = self.generate(seed);
+            if let Err((property, message)) = self.check(&case) {
+                let shrunk = self.shrink(&case, &property);
+                // Report how the shrunk case fails rather than the original.
+                let message = self.check(&shrunk).err().map_or(message, |(_, m)| m);
+                return Err(Box::new(FuzzFailure {
+                    seed,
+                    property,
+                    message,
+                    original: case,
+                    shrunk,
+                }));
+            }
+        }
+        Ok(self.cases)
+    }
+
+    /// The first property `case` breaks, with what went wrong.
+    pub fn check(&self, case: &FuzzCase) -> Result<(), (Property, String)> {
+        guard(Property::Deterministic, || self.deterministic(case))?;
+        guard(Property::SnapshotRestore, || self.snapshot_restore(case))?;
+        guard(Property::MemoryBackends, || self.memory_backends(case))?;
+        guard(Property::Unbounded, || self.unbounded(case))?;
+        for (name, check) in self.properties.iter() {
+            guard(Property::Custom(name.clone()), || check(case))?;
+        }
+        Ok(())
+    }
+
+    /// Keeps replacing `case` with the first simpler case that still breaks `property`.
+    pub fn shrink(&self, case: &FuzzCase, property: &Property) -> FuzzCase {
+        let mut case = case.clone();
+        let mut tries = 0;
+        'simpler: while tries < MAX_SHRINKS {
+            for candidate in case.simpler() {
+                tries += 1;
+                if matches!(self.check(&candidate), Err((broken, _)) if broken == *property) {
+                    case = candidate;
+                    continue 'simpler;
+                }
+                if tries == MAX_SHRINKS {
+                    break;
+                }
+            }
+            break;
+        }
+        case
+    }
+
+    fn program<M: Memory<Cell = i128>>(&self, case: &FuzzCase, limits: Limits) -> FuzzProgram<M> {
+        Program::from_cells(case.words(), &case.inputs)
+            .with_memory::<M>()
+            .with_limits(limits)
+    }
+
+    fn run_case<M: Memory<Cell = i128>>(&self, case: &FuzzCase) -> Run {
+        finish(&mut self.program::<M>(case, self.limits), vec![])
+    }
+
+    fn deterministic(&self, case: &FuzzCase) -> Result<(), String> {
+        let first = self.run_case::<VecMemory>(case);
+        first.compare(&self.run_case::<VecMemory>(case))
+    }
+
+    /// Runs halfway, snapshots, and restores the snapshot into a fresh program given
+    /// the rest of the inputs and the rest of the step budget.
+    fn snapshot_restore(&self, case: &FuzzCase) -> Result<(), String> {
+        let mut reference = self.program::<VecMemory>(case, self.limits);
+        let full = finish(&mut reference, vec![]);
+        let half = reference.steps_taken().unwrap_or(0) / 2;
+
+        let mut program = self.program::<VecMemory>(case, self.limits);
+        let mut outputs = vec![];
+        for step in 0..half {
+            match program.execute() {
+                Ok(StepResult::Continued) => {}
+                Ok(StepResult::Output(value)) => outputs.push(value),
+                end => return Err(format!("ended with {:?} after {} steps", end, step)),
+            }
+        }
+        let snapshot = program.snapshot();
+        let consumed = case.inputs.len() - program.num_inputs();
+        let limits = Limits {
+            max_steps: self.limits.max_steps.map(|steps| steps - half),
+            ..self.limits
+        };
+        let mut resumed = Program::from_cells(vec![], &case.inputs[consumed..]).with_limits(limits);
+        resumed.restore(&snapshot);
+        let mut rest = finish(&mut resumed, outputs);
+        // The resumed program only counts the steps it took itself.
+        if let Err(IntcodeError::LimitExceeded {
+            limit: Limit::Steps(steps),
+            ..
+        }) = &mut rest.end
+        {
+            *steps += half;
+        }
+        full.compare(&rest)
+    }
+
+    fn memory_backends(&self, case: &FuzzCase) -> Result<(), String> {
+        let vec = self.run_case::<VecMemory>(case);
+        vec.compare(&self.run_case::<HashMemory>(case))
+            .map_err(|e| format!("HashMemory {}", e))?;
+        vec.compare(&self.run_case::<PagedMemory>(case))
+            .map_err(|e| format!("PagedMemory {}", e))
+    }
+
+    /// Only panics break this, which `guard` reports. Memory isn't compared, as a far
+    /// write would make it too long to list.
+    fn unbounded(&self, case: &FuzzCase) -> Result<(), String> {
+        let words = case.words();
+        decompile(&ControlFlowGraph::new(&Disassembly::new(words.clone())));
+
+        let limits = Limits {
+            max_memory: None,
+            ..self.limits
+        };
+        for &policy in [None, Some(Policy::Warn), Some(Policy::Trap)].iter() {
+            let program = Program::from_cells(words.clone(), &case.inputs).with_limits(limits);
+            let mut program: FuzzProgram<VecMemory> = match policy {
+                Some(policy) => program.with_access_policy(AccessPolicy::all(policy)),
+                None => program,
+            };
+            while let Ok(StepResult::Continued) | Ok(StepResult::Output(_)) = program.execute() {}
+        }
+
+        let steps = self.limits.max_steps.unwrap_or(MAX_STEPS);
+        let symbolic = SymbolicProgram::new(words).steps(steps);
+        let _ = case
+            .inputs
+            .iter()
+            .fold(symbolic, |symbolic, &input| symbolic.input(input))
+            .run();
+        Ok(())
+    }
+}
+/// Runs `check`, reporting a panic in it as breaking `Property::NoPanic`.
+fn guard<F>(property: Property, check: F) -> Result<(), (Property, String)>
+where
+    F: FnOnce() -> Result<(), String>,
+{
+    match panic::catch_unwind(AssertUnwindSafe(check)) {
+        Ok(result) => result.map_err(|message| (property, message)),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            let message = format!("checking {} panicked: {}", property, message);
+            Err((Property::NoPanic, message))
+        }
+    }
+}
+
+impl Default for Fuzzer {
+    fn default() -> Self {
+        Fuzzer::new()
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+
+/// What one executed instruction changed, enough to undo it.
+#[derive(Debug, Clone)]
+pub(crate) struct Change<C> {
+    pub ip: usize,
+    pub relative_base: i128,
+    /// Addresses written and the values they held before, in the order written.
+    pub writes: Vec<(usize, C)>,
+    pub input: Option<C>,
+    pub output: Option<C>,
+}
+
+/// A point in a program's history to rewind to; see `Program::checkpoint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Checkpoint {
+    /// Restores the history has been cleared by before this checkpoint.
+    generation: u64,
+    steps: u64,
+}
+impl Checkpoint {
+    /// Instructions executed between enabling history, or the last `Program::restore`,
+    /// and this checkpoint.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+/// The undo log of a program, keeping at most `capacity` changes.
+#[derive(Debug, Clone)]
+pub(crate) struct History<C> {
+    capacity: usize,
+    changes: VecDeque<Change<C>>,
+    /// Instructions executed minus instructions undone, since the last clear.
+    steps: u64,
+    /// Times the log has been cleared, so checkpoints from before don't match.
+    generation: u64,
+    /// Inputs given back by undoing reads, the next one to read last.
+    replay: Vec<C>,
+}
+impl<C> History<C> {
+    pub fn new(capacity: usize) -> Self {
+        History {
+            capacity,
+            changes: VecDeque::new(),
+            steps: 0,
+            generation: 0,
+            replay: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            generation: self.generation,
+            steps: self.steps,
+        }
+    }
+
+    /// How many undos get back to `checkpoint`, if it's still in the log.
+    pub fn distance(&self, checkpoint: Checkpoint) -> Option<usize> {
+        if checkpoint.generation != self.generation {
+            return None;
+        }
+        let distance = self.steps.checked_sub(checkpoint.steps)?;
+        if distance <= self.changes.len() as u64 {
+            Some(distance as usize)
+        } else {
+            None
+        }
+    }
+
+    /// How many undos it takes to undo the most recent write to `address`.
+    pub fn distance_to_write(&self, address: usize) -> Option<usize> {
+        let position = self
+            .changes
+            .iter()
+            .rposition(|change| change.writes.iter().any(|&(written, _)| written == address))?;
+        Some(self.changes.len() - position)
+    }
+
+    pub fn push(&mut self, change: Change<C>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.changes.len() == self.capacity {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(change);
+        self.steps += 1;
+    }
+
+    /// Takes the most recent change off the log. Its input, if any, is read again next.
+    pub fn pop(&mut self) -> Option<Change<C>>
+    where
+        C: Clone,
+    {
+        let change = self.changes.pop_back()?;
+        self.steps -= 1;
+        if let Some(input) = &change.input {
+            self.replay.push(input.clone());
+        }
+        Some(change)
+    }
+
+    pub fn replayed_input(&mut self) -> Option<C> {
+        self.replay.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.changes.clear();
+        self.replay.clear();
+        self.steps = 0;
+        self.generation += 1;
+    }
+}
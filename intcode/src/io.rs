@@ -0,0 +1,138 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::str::FromStr;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/// Where a program reads its inputs from.
+pub trait InputSource<C = i128> {
+    /// Returns the next input, or `None` if the program has to wait for one.
+    fn next_input(&mut self) -> Option<C>;
+}
+
+/// Where a program writes its outputs to, on top of returning them from `execute`.
+pub trait OutputSink<C = i128> {
+    fn send_output(&mut self, value: C);
+}
+
+/// The default source: inputs queued with `Program::send_input`, dropped once consumed.
+impl<C> InputSource<C> for VecDeque<C> {
+    fn next_input(&mut self) -> Option<C> {
+        self.pop_front()
+    }
+}
+
+/// The default sink: outputs are only returned to the caller.
+impl<C> OutputSink<C> for () {
+    fn send_output(&mut self, _value: C) {}
+}
+
+impl<C> OutputSink<C> for Vec<C> {
+    fn send_output(&mut self, value: C) {
+        self.push(value);
+    }
+}
+
+impl<C> OutputSink<C> for VecDeque<C> {
+    fn send_output(&mut self, value: C) {
+        self.push_back(value);
+    }
+}
+
+/// Reads without blocking, so an empty or closed channel makes the program wait.
+impl<C> InputSource<C> for Receiver<C> {
+    fn next_input(&mut self) -> Option<C> {
+        self.try_recv().ok()
+    }
+}
+
+/// Outputs sent after the receiver hangs up are dropped.
+impl<C> OutputSink<C> for Sender<C> {
+    fn send_output(&mut self, value: C) {
+        let _ = self.send(value);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IterInput<T>(pub T);
+impl<C, T: Iterator<Item = C>> InputSource<C> for IterInput<T> {
+    fn next_input(&mut self) -> Option<C> {
+        self.0.next()
+    }
+}
+
+#[derive(Clone)]
+pub struct FnInput<F>(pub F);
+impl<C, F: FnMut() -> Option<C>> InputSource<C> for FnInput<F> {
+    fn next_input(&mut self) -> Option<C> {
+        (self.0)()
+    }
+}
+
+#[derive(Clone)]
+pub struct FnOutput<F>(pub F);
+impl<C, F: FnMut(C)> OutputSink<C> for FnOutput<F> {
+    fn send_output(&mut self, value: C) {
+        (self.0)(value)
+    }
+}
+
+/// Reads one integer per line from stdin, blocking until a line arrives.
+/// Lines that are not integers are skipped and end of input makes the program wait.
+#[derive(Debug, Clone, Default)]
+pub struct StdinInput;
+impl<C: FromStr> InputSource<C> for StdinInput {
+    fn next_input(&mut self) -> Option<C> {
+        for line in io::stdin().lock().lines() {
+            match line.ok()?.trim().parse() {
+                Ok(value) => return Some(value),
+                Err(_) => continue,
+            }
+        }
+        None
+    }
+}
+
+/// A queue shared between its clones, for wiring one program's output
+/// straight into another program's input, even across threads.
+#[derive(Debug)]
+pub struct Pipe<C = i128> {
+    queue: Arc<Mutex<VecDeque<C>>>,
+}
+impl<C> Clone for Pipe<C> {
+    fn clone(&self) -> Self {
+        Pipe {
+            queue: Arc::clone(&self.queue),
+        }
+    }
+}
+impl<C> Default for Pipe<C> {
+    fn default() -> Self {
+        Pipe {
+            queue: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+}
+impl<C> Pipe<C> {
+    pub fn new() -> Self {
+        Pipe::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap().is_empty()
+    }
+}
+impl<C> InputSource<C> for Pipe<C> {
+    fn next_input(&mut self) -> Option<C> {
+        self.queue.lock().unwrap().pop_front()
+    }
+}
+impl<C> OutputSink<C> for Pipe<C> {
+    fn send_output(&mut self, value: C) {
+        self.queue.lock().unwrap().push_back(value);
+    }
+}
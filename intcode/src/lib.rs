@@ -1,7 +1,64 @@
-use std::cell::{Ref, RefCell};
-use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::env;
-use std::io;
+use std::future;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+mod asm;
+mod cell;
+mod cfg;
+mod debugger;
+mod decompile;
+mod disasm;
+mod error;
+mod executor;
+mod format;
+mod fuzz;
+mod history;
+mod io;
+mod limits;
+mod memory;
+mod network;
+mod policy;
+mod profile;
+mod registry;
+mod symbolic;
+mod threads;
+mod trace;
+
+pub use asm::{assemble, assemble_words};
+pub use cell::{Arithmetic, Cell};
+pub use cfg::{Block, ControlFlowGraph, Edge, EdgeKind, Exit, Subroutine};
+pub use debugger::{Debugger, Stop};
+pub use decompile::decompile;
+pub use disasm::{disassemble, Disassembly, Flow, Instruction, Operand, Target};
+pub use error::IntcodeError;
+pub use executor::{sleep, yield_now, AsyncInput, Channel, Executor, Sleep, YieldNow};
+pub use format::{decode_binary, encode_binary, parse_text, read_program, write_binary};
+pub use fuzz::{FuzzCase, FuzzFailure, Fuzzer, Property};
+pub use history::Checkpoint;
+pub use io::{FnInput, FnOutput, InputSource, IterInput, OutputSink, Pipe, StdinInput};
+pub use limits::{Limit, Limits};
+pub use memory::{HashMemory, Memory, PagedMemory, VecMemory};
+pub use network::{Event, Message, Network, Packets, Pipeline, Ring, Router};
+pub use policy::{Access, AccessPolicy, Policy, Violation};
+pub use profile::{Loop, MemoryTouches, Profile};
+pub use registry::{Action, Call, OpcodeRegistry, Param};
+pub use symbolic::{Expr, Goal, Linear, Method, Outcome, Solution, Stuck, SymbolicProgram};
+pub use threads::ThreadedNetwork;
+pub use trace::{replay, Divergence, Trace, TraceEntry, TraceSink, TraceWriter};
+
+#[cfg(feature = "bigint")]
+pub use num_bigint::BigInt;
+
+use cell::saturate;
+use executor::YIELD_STEPS;
+use format::{read_state, write_state, SavedState};
+use history::{Change, History};
+use limits::Watchdog;
+use policy::Guard;
+use trace::Tracer;
 
 const ADD: i128 = 1;
 const MULTIPLY: i128 = 2;
@@ -14,7 +71,8 @@ const EQUALS: i128 = 8;
 const REL: i128 = 9;
 const STOP: i128 = 99;
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 enum Command {
     ADD,
     MULTIPLY,
@@ -26,6 +84,98 @@ enum Command {
     EQUALS,
     REL,
     STOP,
+    /// An opcode added through an `OpcodeRegistry`.
+    CUSTOM(CustomCommand),
+}
+impl Command {
+    fn num_params(&self) -> usize {
+        match self {
+            Command::ADD | Command::MULTIPLY => OPER_NUM_PARAMS,
+            Command::INPUT | Command::OUTPUT => IO_NUM_PARAMS,
+            Command::JIT | Command::JIF => JUMP_NUM_PARAMS,
+            Command::LESS | Command::EQUALS => CMP_NUM_PARAMS,
+            Command::REL => REL_NUM_PARAMS,
+            Command::STOP => 0,
+            Command::CUSTOM(custom) => custom.num_params as usize,
+        }
+    }
+
+    /// Whether the parameter at `offset` is an address the command writes to.
+    fn is_write(&self, offset: usize) -> bool {
+        match self {
+            Command::ADD | Command::MULTIPLY | Command::INPUT | Command::LESS | Command::EQUALS => {
+                offset == self.num_params() - 1
+            }
+            Command::CUSTOM(custom) => custom.writes & 1 << offset != 0,
+            _ => false,
+        }
+    }
+
+    /// The offset of the last write parameter, if the command has one.
+    fn write_param(&self) -> Option<usize> {
+        (0..self.num_params())
+            .rev()
+            .find(|&offset| self.is_write(offset))
+    }
+
+    fn code(&self) -> i128 {
+        match self {
+            Command::ADD => ADD,
+            Command::MULTIPLY => MULTIPLY,
+            Command::INPUT => INPUT,
+            Command::OUTPUT => OUTPUT,
+            Command::JIT => JIT,
+            Command::JIF => JIF,
+            Command::LESS => LESS,
+            Command::EQUALS => EQUALS,
+            Command::REL => REL,
+            Command::STOP => STOP,
+            Command::CUSTOM(custom) => custom.code as i128,
+        }
+    }
+
+    fn from_mnemonic(mnemonic: &str) -> Option<Command> {
+        match &mnemonic.to_uppercase()[..] {
+            "ADD" => Some(Command::ADD),
+            "MUL" => Some(Command::MULTIPLY),
+            "IN" => Some(Command::INPUT),
+            "OUT" => Some(Command::OUTPUT),
+            "JT" => Some(Command::JIT),
+            "JF" => Some(Command::JIF),
+            "LT" => Some(Command::LESS),
+            "EQ" => Some(Command::EQUALS),
+            "ARB" => Some(Command::REL),
+            "HALT" => Some(Command::STOP),
+            _ => None,
+        }
+    }
+
+    fn mnemonic(&self) -> &'static str {
+        match self {
+            Command::ADD => "ADD",
+            Command::MULTIPLY => "MUL",
+            Command::INPUT => "IN",
+            Command::OUTPUT => "OUT",
+            Command::JIT => "JT",
+            Command::JIF => "JF",
+            Command::LESS => "LT",
+            Command::EQUALS => "EQ",
+            Command::REL => "ARB",
+            Command::STOP => "HALT",
+            // The real name lives in the registry; see `OpcodeRegistry::mnemonic`.
+            Command::CUSTOM(_) => "CUSTOM",
+        }
+    }
+}
+
+/// What decoding needs to know about a registered opcode. The handler and mnemonic stay
+/// in the registry, which keeps `Opcode` small enough for the pre-decoded table to stay fast.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+struct CustomCommand {
+    code: u8,
+    num_params: u8,
+    /// Bit `n` is set if parameter `n` is written to.
+    writes: u8,
 }
 
 const POSITION: u32 = 0;
@@ -39,73 +189,122 @@ const JUMP_NUM_PARAMS: usize = 2;
 const CMP_NUM_PARAMS: usize = 3;
 const REL_NUM_PARAMS: usize = 1;
 
-const WRITE_CMDS: [Command; 5] = [
-    Command::ADD,
-    Command::MULTIPLY,
-    Command::INPUT,
-    Command::LESS,
-    Command::EQUALS,
-];
-
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct Opcode {
     command: Command,
-    modes: Vec<u32>,
+    /// Only the first `command.num_params()` are meaningful; the rest are `POSITION`.
+    modes: [u32; OPER_NUM_PARAMS],
+    raw: i128,
 }
 impl Opcode {
-    pub fn new(opcode: i128) -> Self {
-        let (command, modes) = match opcode % 100 {
-            ADD => (Command::ADD, Opcode::get_modes(opcode, OPER_NUM_PARAMS)),
-            MULTIPLY => (
-                Command::MULTIPLY,
-                Opcode::get_modes(opcode, OPER_NUM_PARAMS),
-            ),
-            INPUT => (Command::INPUT, Opcode::get_modes(opcode, IO_NUM_PARAMS)),
-            OUTPUT => (Command::OUTPUT, Opcode::get_modes(opcode, IO_NUM_PARAMS)),
-            JIT => (Command::JIT, Opcode::get_modes(opcode, JUMP_NUM_PARAMS)),
-            JIF => (Command::JIF, Opcode::get_modes(opcode, JUMP_NUM_PARAMS)),
-            LESS => (Command::LESS, Opcode::get_modes(opcode, CMP_NUM_PARAMS)),
-            EQUALS => (Command::EQUALS, Opcode::get_modes(opcode, CMP_NUM_PARAMS)),
-            REL => (Command::REL, Opcode::get_modes(opcode, REL_NUM_PARAMS)),
-            STOP => (Command::STOP, vec![]),
-            _ => panic!("Invalid opcode: {}", opcode),
+    pub fn new(opcode: i128, ip: usize) -> Result<Self, IntcodeError> {
+        Opcode::decode(opcode, ip, |_| None)
+    }
+
+    /// Like `new`, asking `custom` about codes that aren't standard opcodes.
+    fn decode<F>(opcode: i128, ip: usize, custom: F) -> Result<Self, IntcodeError>
+    where
+        F: Fn(i128) -> Option<Command>,
+    {
+        let command = match opcode % 100 {
+            ADD => Command::ADD,
+            MULTIPLY => Command::MULTIPLY,
+            INPUT => Command::INPUT,
+            OUTPUT => Command::OUTPUT,
+            JIT => Command::JIT,
+            JIF => Command::JIF,
+            LESS => Command::LESS,
+            EQUALS => Command::EQUALS,
+            REL => Command::REL,
+            STOP => Command::STOP,
+            code => match custom(code) {
+                Some(command) => command,
+                None => return Err(IntcodeError::InvalidOpcode { ip, opcode }),
+            },
         };
-        Opcode { command, modes }
+        let modes = Opcode::get_modes(opcode, command.num_params(), ip)?;
+        Ok(Opcode {
+            command,
+            modes,
+            raw: opcode,
+        })
     }
 
-    fn get_modes(opcode: i128, num_params: usize) -> Vec<u32> {
-        let mut digits: Vec<u32> = opcode
-            .to_string()
-            .chars()
-            .map(|c| c.to_digit(10).unwrap())
-            .rev()
-            .collect();
-        while digits.len() < num_params + LEN_COMMAND {
-            digits.push(0);
+    /// Reads one mode digit per parameter. Any digit past the last parameter
+    /// must be zero, since it would describe a parameter that doesn't exist.
+    fn get_modes(
+        opcode: i128,
+        num_params: usize,
+        ip: usize,
+    ) -> Result<[u32; OPER_NUM_PARAMS], IntcodeError> {
+        let mut modes = [POSITION; OPER_NUM_PARAMS];
+        let mut digits = opcode / 10_i128.pow(LEN_COMMAND as u32);
+        for mode in modes.iter_mut().take(num_params) {
+            *mode = (digits % 10) as u32;
+            if *mode > RELATIVE {
+                return Err(IntcodeError::InvalidMode {
+                    ip,
+                    opcode,
+                    mode: *mode,
+                });
+            }
+            digits /= 10;
+        }
+        while digits % 10 == 0 && digits != 0 {
+            digits /= 10;
+        }
+        if digits != 0 {
+            return Err(IntcodeError::InvalidMode {
+                ip,
+                opcode,
+                mode: (digits % 10) as u32,
+            });
         }
-        digits[LEN_COMMAND..].to_vec()
+        Ok(modes)
     }
 
-    fn to_command_str(&self, params: &Vec<i128>) -> String {
+    /// Decodes a cell, which for wide cell types may not even fit an `i128`.
+    fn from_cell<C: Cell, F>(raw: &C, ip: usize, custom: F) -> Result<Self, IntcodeError>
+    where
+        F: Fn(i128) -> Option<Command>,
+    {
+        match raw.to_i128() {
+            Some(opcode) => Opcode::decode(opcode, ip, custom),
+            None => Err(IntcodeError::InvalidOpcode {
+                ip,
+                opcode: saturate(raw),
+            }),
+        }
+    }
+
+    fn modes(&self) -> &[u32] {
+        &self.modes[..self.command.num_params()]
+    }
+
+    fn to_command_str<C: Cell>(self, params: &[C]) -> String {
         match self.command {
-            Command::ADD => format!("Storing {} to address {}", params[0] + params[1], params[2]),
-            Command::MULTIPLY => {
-                format!("Storing {} to address {}", params[0] * params[1], params[2])
-            }
+            Command::ADD => match params[0].checked_add(&params[1]) {
+                Some(sum) => format!("Storing {} to address {}", sum, params[2]),
+                None => String::from("Overflowing"),
+            },
+            Command::MULTIPLY => match params[0].checked_mul(&params[1]) {
+                Some(product) => format!("Storing {} to address {}", product, params[2]),
+                None => String::from("Overflowing"),
+            },
             Command::INPUT => format!("Storing input to address {}", params[0]),
             Command::OUTPUT => format!("Outputting {}", params[0]),
             Command::JIT => {
-                if params[0] != 0 {
+                if !params[0].is_zero() {
                     format!("Jumping to instruction at address {}", params[1])
                 } else {
-                    format!("Not jumping")
+                    String::from("Not jumping")
                 }
             }
             Command::JIF => {
-                if params[0] == 0 {
+                if params[0].is_zero() {
                     format!("Jumping to instruction at address {}", params[1])
                 } else {
-                    format!("Not jumping")
+                    String::from("Not jumping")
                 }
             }
             Command::LESS => {
@@ -123,140 +322,792 @@ impl Opcode {
                 }
             }
             Command::REL => format!("Increasing relative base by {}", params[0]),
-            Command::STOP => format!("Stopping"),
+            Command::STOP => String::from("Stopping"),
+            Command::CUSTOM(custom) => format!("Calling opcode {} with {:?}", custom.code, params),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult<C = i128> {
+    /// The instruction ran and the program can keep going.
+    Continued,
+    /// The instruction produced an output.
+    Output(C),
+    /// The next instruction is an input and no input is queued; nothing was executed.
+    NeedsInput,
+    /// The program reached a stop instruction.
+    Halted,
+}
+impl<C> StepResult<C> {
+    pub fn is_halted(&self) -> bool {
+        matches!(self, StepResult::Halted)
+    }
+}
+
+/// The outputs of a run and the step that ended it.
+pub type RunResult<C = i128> = Result<(Vec<C>, StepResult<C>), IntcodeError>;
+
+pub fn parse_program(program_string: &str) -> Result<Vec<i128>, IntcodeError> {
+    parse_cells(program_string)
+}
+
+pub fn parse_cells<C: Cell>(program_string: &str) -> Result<Vec<C>, IntcodeError> {
+    program_string
+        .split(',')
+        .enumerate()
+        .map(|(i, val)| match val.trim().parse() {
+            Ok(parsed) => Ok(parsed),
+            Err(_) => Err(IntcodeError::Parse {
+                index: i,
+                value: String::from(val),
+            }),
+        })
+        .collect()
+}
+
+/// The machine state of a program at one point in time: memory, ip and relative base.
+/// Input and output wiring is not part of a snapshot.
+#[derive(Debug, Clone)]
+pub struct Snapshot<M = VecMemory> {
+    memory: M,
+    ip: usize,
+    relative_base: i128,
+}
+impl<M> Snapshot<M> {
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct Program {
-    memory: RefCell<HashMap<usize, i128>>,
+pub struct Program<I = VecDeque<i128>, O = (), M: Memory = VecMemory> {
+    memory: M,
     ip: usize,
-    inputs: RefCell<Vec<i128>>,
-    input_index: usize,
-    relative_base: usize,
+    input: I,
+    output: O,
+    relative_base: i128,
+    arithmetic: Arithmetic,
     debug: bool,
+    trace: Option<Tracer<M::Cell>>,
+    profile: Option<Box<Profile>>,
+    registry: Option<Arc<OpcodeRegistry<M::Cell>>>,
+    exit_code: Option<M::Cell>,
+    watchdog: Option<Watchdog>,
+    guard: Option<Box<Guard>>,
+    history: Option<Box<History<M::Cell>>>,
+    decoded: Arc<[Option<Opcode>]>,
 }
 impl Program {
-    pub fn new(program_string: &str, inputs: &Vec<i128>) -> Self {
-        let memory = program_string
-            .split(",")
-            .enumerate()
-            .map(|(i, val)| (i, val.parse().unwrap()))
-            .collect::<HashMap<usize, i128>>();
+    pub fn new(program_string: &str, inputs: &[i128]) -> Result<Self, IntcodeError> {
+        Program::load(program_string, inputs)
+    }
+}
+impl<C: Cell> Program<VecDeque<C>, (), VecMemory<C>> {
+    /// Like `new`, for any cell type: `Program::<_, _, VecMemory<i64>>::load(..)`.
+    pub fn load(program_string: &str, inputs: &[C]) -> Result<Self, IntcodeError> {
+        Ok(Program::from_cells(parse_cells(program_string)?, inputs))
+    }
+
+    /// Loads a program file, binary or text; see `read_program`.
+    pub fn from_path(path: impl AsRef<Path>, inputs: &[C]) -> Result<Self, IntcodeError> {
+        Ok(Program::from_cells(read_program(path)?, inputs))
+    }
+
+    /// Resumes a program saved with `save_state`. Settings such as custom opcodes or a
+    /// profiler aren't part of the file and have to be set up again.
+    pub fn load_state(path: impl AsRef<Path>) -> Result<Self, IntcodeError> {
+        let state = read_state(path)?;
+        let mut program = Program::from_cells(state.memory, &state.inputs);
+        for (address, value) in state.sparse {
+            program.memory.set(address, value);
+        }
+        program.ip = state.ip;
+        program.relative_base = state.relative_base;
+        Ok(program)
+    }
+
+    pub fn from_cells(cells: Vec<C>, inputs: &[C]) -> Self {
+        let memory = VecMemory::from_cells(cells);
+        let decoded = predecode(&memory, None);
         Program {
-            memory: RefCell::new(memory),
+            memory,
             ip: 0,
-            inputs: RefCell::new(inputs.clone()),
-            input_index: 0,
+            input: inputs.iter().cloned().collect(),
+            output: (),
             relative_base: 0,
+            arithmetic: Arithmetic::default(),
             debug: env::var_os("DEBUG").is_some(),
+            trace: None,
+            profile: None,
+            registry: None,
+            exit_code: None,
+            watchdog: None,
+            guard: None,
+            history: None,
+            decoded,
         }
     }
+}
+impl<O: OutputSink<M::Cell>, M: Memory> Program<VecDeque<M::Cell>, O, M> {
+    pub fn send_input(&mut self, input: M::Cell) {
+        self.input.push_back(input);
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.input.len()
+    }
+
+    /// Saves memory, ip, relative base and the queued inputs to `path`, for
+    /// `Program::load_state` to carry on exactly where this program is.
+    pub fn save_state(&self, path: impl AsRef<Path>) -> Result<(), IntcodeError> {
+        let state = SavedState {
+            ip: self.ip,
+            relative_base: self.relative_base,
+            inputs: self.input.iter().cloned().collect(),
+            memory: self.memory.to_vec(),
+            sparse: self.memory.sparse_cells(),
+        };
+        write_state(path, &state)
+    }
+}
+impl<I: AsyncInput<M::Cell>, O: OutputSink<M::Cell>, M: Memory> Program<I, O, M> {
+    /// Like `run`, but waiting for input suspends the program instead of failing, so it
+    /// can run as one task among many on an `Executor`. A program that runs long without
+    /// reading yields to the other tasks every so often. Fails with `MissingInput` once
+    /// the input says nothing more will arrive.
+    pub async fn run_async(&mut self) -> Result<Vec<M::Cell>, IntcodeError> {
+        let mut outputs = vec![];
+        let mut steps = 0;
+        loop {
+            match self.execute()? {
+                StepResult::Output(value) => outputs.push(value),
+                StepResult::Halted => return Ok(outputs),
+                StepResult::NeedsInput => {
+                    let input = &mut self.input;
+                    if !future::poll_fn(|cx| input.poll_ready(cx)).await {
+                        return Err(IntcodeError::MissingInput {
+                            ip: self.ip,
+                            opcode: saturate(&self.val_at(self.ip)),
+                        });
+                    }
+                    steps = 0;
+                }
+                StepResult::Continued => {}
+            }
+            steps += 1;
+            if steps >= YIELD_STEPS {
+                steps = 0;
+                yield_now().await;
+            }
+        }
+    }
+}
+impl<I: InputSource<M::Cell>, O: OutputSink<M::Cell>, M: Memory> Program<I, O, M> {
+    /// Replaces where the program reads inputs from. Inputs still queued on the old source are dropped.
+    pub fn with_input<J: InputSource<M::Cell>>(self, input: J) -> Program<J, O, M> {
+        Program {
+            memory: self.memory,
+            ip: self.ip,
+            input,
+            output: self.output,
+            relative_base: self.relative_base,
+            arithmetic: self.arithmetic,
+            debug: self.debug,
+            trace: self.trace,
+            profile: self.profile,
+            registry: self.registry,
+            exit_code: self.exit_code,
+            watchdog: self.watchdog,
+            guard: self.guard,
+            history: self.history,
+            decoded: self.decoded,
+        }
+    }
+
+    /// Replaces where the program writes outputs to.
+    pub fn with_output<P: OutputSink<M::Cell>>(self, output: P) -> Program<I, P, M> {
+        Program {
+            memory: self.memory,
+            ip: self.ip,
+            input: self.input,
+            output,
+            relative_base: self.relative_base,
+            arithmetic: self.arithmetic,
+            debug: self.debug,
+            trace: self.trace,
+            profile: self.profile,
+            registry: self.registry,
+            exit_code: self.exit_code,
+            watchdog: self.watchdog,
+            guard: self.guard,
+            history: self.history,
+            decoded: self.decoded,
+        }
+    }
+
+    /// Moves the program's cells into a different memory backend.
+    pub fn with_memory<N: Memory<Cell = M::Cell>>(self) -> Program<I, O, N> {
+        let mut memory = N::from_cells(self.memory.to_vec());
+        for (address, value) in self.memory.sparse_cells() {
+            memory.set(address, value);
+        }
+        Program {
+            memory,
+            ip: self.ip,
+            input: self.input,
+            output: self.output,
+            relative_base: self.relative_base,
+            arithmetic: self.arithmetic,
+            debug: self.debug,
+            trace: self.trace,
+            profile: self.profile,
+            registry: self.registry,
+            exit_code: self.exit_code,
+            watchdog: self.watchdog,
+            guard: self.guard,
+            history: self.history,
+            decoded: self.decoded,
+        }
+    }
+
+    /// Records every instruction executed from now on into `sink`. The caller keeps its
+    /// own handle to read the trace back; clones of the program record into the same sink.
+    pub fn with_trace<S>(mut self, sink: Arc<Mutex<S>>) -> Self
+    where
+        S: TraceSink<M::Cell> + Send + 'static,
+    {
+        self.trace = Some(Tracer(sink));
+        self
+    }
 
-    pub fn memory(&self) -> Ref<HashMap<usize, i128>> {
-        self.memory.borrow()
+    /// Chooses what happens when ADD or MULTIPLY overflows the cell type.
+    /// The default is `Arithmetic::Checked`.
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self
     }
 
-    fn val_at(&self, index: usize) -> i128 {
-        match self.memory().get(&index) {
-            Some(&val) => val,
-            None => 0,
+    /// Teaches the program the opcodes in `registry` on top of the standard ones.
+    /// Clones of the program share the registry.
+    pub fn with_opcodes(mut self, registry: OpcodeRegistry<M::Cell>) -> Self {
+        self.decoded = predecode(&self.memory, Some(&registry));
+        self.registry = Some(Arc::new(registry));
+        self
+    }
+
+    /// The code a custom opcode halted with through `Action::Exit`.
+    pub fn exit_code(&self) -> Option<&M::Cell> {
+        self.exit_code.as_ref()
+    }
+
+    /// Stops the program with `IntcodeError::LimitExceeded` once it goes past `limits`.
+    /// Usage counts from here, and again from every `restore`.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.watchdog = Some(Watchdog::new(limits));
+        self
+    }
+
+    /// Instructions executed under the current limits, if any are set.
+    pub fn steps_taken(&self) -> Option<u64> {
+        self.watchdog.map(|watchdog| watchdog.steps)
+    }
+
+    /// Watches for suspicious memory accesses from now on; see `AccessPolicy`. Code is
+    /// what the disassembler reaches from address 0 in memory as it is now.
+    pub fn with_access_policy(mut self, policy: AccessPolicy) -> Self {
+        let words = self.memory.to_vec().iter().map(saturate).collect();
+        let mut guard = Guard::new(policy, words);
+        let far = self.memory.sparse_cells().into_iter().map(|(address, _)| address);
+        guard.record_writes(&far.collect::<Vec<usize>>());
+        self.guard = Some(Box::new(guard));
+        self
+    }
+
+    /// Accesses the access policy let through with `Policy::Warn`, oldest first.
+    pub fn violations(&self) -> &[Violation] {
+        match &self.guard {
+            Some(guard) => guard.violations(),
+            None => &[],
+        }
+    }
+
+    /// Keeps an undo log of the last `capacity` instructions, so the program can step
+    /// backwards. Outputs already sent to the output sink stay sent.
+    pub fn with_history(mut self, capacity: usize) -> Self {
+        self.history = Some(Box::new(History::new(capacity)));
+        self
+    }
+
+    /// How many instructions can be undone.
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, |history| history.len())
+    }
+
+    /// Marks the current point in history for `rewind`. `None` without history.
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.history.as_ref().map(|history| history.checkpoint())
+    }
+
+    /// Undoes the last instruction and returns what it did. Inputs it read are read
+    /// again when the program runs forward.
+    pub fn step_back(&mut self) -> Option<StepResult<M::Cell>> {
+        let change = self.history.as_mut()?.pop()?;
+        for (address, old) in change.writes.into_iter().rev() {
+            self.memory.set(address, old);
         }
+        self.ip = change.ip;
+        self.relative_base = change.relative_base;
+        self.exit_code = None;
+        Some(match change.output {
+            Some(output) => StepResult::Output(output),
+            None => StepResult::Continued,
+        })
+    }
+
+    /// Goes back to `checkpoint` and returns the outputs taken back, latest first.
+    /// Does nothing and returns `None` if the checkpoint has fallen out of the history.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) -> Option<Vec<M::Cell>> {
+        let distance = self.history.as_ref()?.distance(checkpoint)?;
+        Some(self.step_back_by(distance))
+    }
+
+    /// Goes back to just before the last write to `address` and returns the outputs
+    /// taken back, latest first. `None` if no write to it is in the history.
+    pub fn run_back_to_write(&mut self, address: usize) -> Option<Vec<M::Cell>> {
+        let distance = self.history.as_ref()?.distance_to_write(address)?;
+        Some(self.step_back_by(distance))
+    }
+
+    fn step_back_by(&mut self, steps: usize) -> Vec<M::Cell> {
+        (0..steps)
+            .filter_map(|_| match self.step_back() {
+                Some(StepResult::Output(output)) => Some(output),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Starts counting executions, memory touches and time blocked on input.
+    pub fn with_profiler(mut self) -> Self {
+        self.profile = Some(Box::new(Profile::new()));
+        self
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
     }
 
-    pub fn set(&self, index: usize, val: i128) {
-        self.memory.borrow_mut().insert(index, val);
+    /// Stops profiling and hands back what was collected.
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take().map(|profile| *profile)
     }
 
-    pub fn send_input(&mut self, input: i128) {
-        self.inputs.borrow_mut().push(input);
+    pub fn input(&self) -> &I {
+        &self.input
     }
 
-    fn next_opcode(&self) -> Opcode {
-        Opcode::new(self.val_at(self.ip))
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
     }
 
-    fn get_params(&self, opcode: &Opcode) -> Vec<i128> {
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        &mut self.output
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.memory
+    }
+
+    /// Writes the current memory to `path` in the binary format, to load back with
+    /// `Program::from_path`. Fails if the program wrote far past its cells, which the
+    /// format has no room for.
+    pub fn dump_memory(&self, path: impl AsRef<Path>) -> Result<(), IntcodeError> {
+        if let Some((address, _)) = self.memory.sparse_cells().first() {
+            return Err(IntcodeError::Binary {
+                position: *address,
+                message: String::from("cell is too far past the others to store"),
+            });
+        }
+        write_binary(path, &self.memory.to_vec())
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> i128 {
+        self.relative_base
+    }
+
+    /// Decodes the instruction at the ip, or `None` if the ip points at something invalid.
+    pub fn current_instruction(&self) -> Option<Instruction> {
+        Instruction::decode_cells(&self.memory, self.ip, self.registry.as_deref())
+    }
+
+    /// Captures the machine state. With `PagedMemory` this shares every page with the
+    /// running program until one side writes to it.
+    pub fn snapshot(&self) -> Snapshot<M>
+    where
+        M: Clone,
+    {
+        Snapshot {
+            memory: self.memory.clone(),
+            ip: self.ip,
+            relative_base: self.relative_base,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot<M>)
+    where
+        M: Clone,
+    {
+        self.memory = snapshot.memory.clone();
+        self.ip = snapshot.ip;
+        self.relative_base = snapshot.relative_base;
+        self.exit_code = None;
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        if let Some(watchdog) = &mut self.watchdog {
+            *watchdog = Watchdog::new(watchdog.limits);
+        }
+    }
+
+    fn val_at(&self, index: usize) -> M::Cell {
+        self.memory.get(index)
+    }
+
+    pub fn set(&mut self, index: usize, val: M::Cell) {
+        self.memory.set(index, val);
+    }
+
+    /// Uses the pre-decoded instruction at `ip` while the opcode word there still matches
+    /// it, and decodes from memory if the program has rewritten that word since. Fails
+    /// for an instruction that runs up against the end of the address space, so its
+    /// operands and the instruction after it can be found by adding to `ip`.
+    fn next_opcode(&self) -> Result<Opcode, IntcodeError> {
+        let raw = self.val_at(self.ip);
+        let opcode = match self.decoded.get(self.ip) {
+            Some(Some(opcode)) if raw.to_i128() == Some(opcode.raw) => *opcode,
+            _ => Opcode::from_cell(&raw, self.ip, |code| self.custom_command(code))?,
+        };
+        match self.ip.checked_add(opcode.modes().len() + 1) {
+            Some(_) => Ok(opcode),
+            None => Err(self.overflow(&opcode)),
+        }
+    }
+
+    fn custom_command(&self, code: i128) -> Option<Command> {
+        self.registry.as_ref()?.command(code)
+    }
+
+    fn overflow(&self, opcode: &Opcode) -> IntcodeError {
+        IntcodeError::Overflow {
+            ip: self.ip,
+            opcode: opcode.raw,
+        }
+    }
+
+    fn to_address(&self, opcode: &Opcode, address: i128) -> Result<usize, IntcodeError> {
+        if address < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                ip: self.ip,
+                opcode: opcode.raw,
+                address,
+            });
+        }
+        usize::try_from(address).map_err(|_| self.overflow(opcode))
+    }
+
+    /// The address `value + base`, where `base` is 0 or the relative base.
+    fn cell_address(
+        &self,
+        opcode: &Opcode,
+        value: &M::Cell,
+        base: i128,
+    ) -> Result<usize, IntcodeError> {
+        match value.to_i128().and_then(|value| value.checked_add(base)) {
+            Some(address) => self.to_address(opcode, address),
+            None if *value < M::Cell::default() => Err(IntcodeError::NegativeAddress {
+                ip: self.ip,
+                opcode: opcode.raw,
+                address: saturate(value),
+            }),
+            None => Err(self.overflow(opcode)),
+        }
+    }
+
+    /// Addresses of the operands this instruction reads through memory.
+    fn read_addresses(&self, opcode: &Opcode) -> Vec<usize> {
         opcode
-            .modes
+            .modes()
             .iter()
             .enumerate()
-            .map(|(offset, &mode)| {
-                let is_write =
-                    offset == opcode.modes.len() - 1 && WRITE_CMDS.contains(&(opcode.command));
+            .filter(|&(offset, _)| !opcode.command.is_write(offset))
+            .filter_map(|(offset, &mode)| {
+                let raw = self.val_at(self.ip + offset + 1);
                 match mode {
-                    POSITION => {
-                        if is_write {
-                            self.val_at(self.ip + offset + 1)
-                        } else {
-                            self.val_at(self.val_at(self.ip + offset + 1) as usize)
-                        }
-                    }
-                    IMMEDIATE => self.val_at(self.ip + offset + 1),
-                    RELATIVE => {
-                        if is_write {
-                            self.val_at(self.ip + offset + 1) + self.relative_base as i128
-                        } else {
-                            self.val_at(
-                                (self.val_at(self.ip + offset + 1) + self.relative_base as i128)
-                                    as usize,
-                            )
-                        }
-                    }
-                    _ => panic!("Invalid modes for opcode: {:?}", opcode),
+                    POSITION => self.cell_address(opcode, &raw, 0).ok(),
+                    RELATIVE => self.cell_address(opcode, &raw, self.relative_base).ok(),
+                    _ => None,
                 }
             })
             .collect()
     }
 
-    pub fn num_inputs(&self) -> usize {
-        self.inputs.borrow().len() - self.input_index
+    /// Where a taken JIT or JIF at `ip` lands, worked out before it runs.
+    fn jump_target(&self, opcode: &Opcode) -> Result<Option<usize>, IntcodeError> {
+        let taken = match opcode.command {
+            Command::JIT => !self.param(opcode, 0)?.is_zero(),
+            Command::JIF => self.param(opcode, 0)?.is_zero(),
+            _ => return Ok(None),
+        };
+        if !taken {
+            return Ok(None);
+        }
+        let target = self.cell_address(opcode, &self.param(opcode, 1)?, 0)?;
+        Ok(Some(target).filter(|&target| target != self.ip + JUMP_NUM_PARAMS + 1))
+    }
+
+    /// The value of the read parameter at `offset`.
+    fn param(&self, opcode: &Opcode, offset: usize) -> Result<M::Cell, IntcodeError> {
+        let raw = self.val_at(self.ip + offset + 1);
+        match opcode.modes[offset] {
+            IMMEDIATE => Ok(raw),
+            POSITION => Ok(self.val_at(self.cell_address(opcode, &raw, 0)?)),
+            _ => Ok(self.val_at(self.cell_address(opcode, &raw, self.relative_base)?)),
+        }
+    }
+
+    /// The address the write parameter at `offset` points to.
+    fn target(&self, opcode: &Opcode, offset: usize) -> Result<usize, IntcodeError> {
+        let raw = self.val_at(self.ip + offset + 1);
+        match opcode.modes[offset] {
+            RELATIVE => self.cell_address(opcode, &raw, self.relative_base),
+            _ => self.cell_address(opcode, &raw, 0),
+        }
+    }
+
+    /// Resolves every parameter; a write parameter resolves to its target address.
+    fn get_params(&self, opcode: &Opcode) -> Result<Vec<M::Cell>, IntcodeError> {
+        (0..opcode.command.num_params())
+            .map(|offset| {
+                if opcode.command.is_write(offset) {
+                    let target = self.target(opcode, offset)? as i128;
+                    M::Cell::from_i128(target).ok_or_else(|| self.overflow(opcode))
+                } else {
+                    self.param(opcode, offset)
+                }
+            })
+            .collect()
     }
 
     pub fn needs_input(&self) -> bool {
-         self.next_opcode().command == Command::INPUT
+        saturate(&self.val_at(self.ip)) % 100 == INPUT
     }
 
-    pub fn run_until_blocked_or_done(&mut self) -> (Vec<i128>, bool) {
+    /// Executes until `stop` returns true for a step, or the program halts or blocks on input.
+    /// Returns the outputs produced along the way and the result of the last step.
+    pub fn run_until<F>(&mut self, mut stop: F) -> RunResult<M::Cell>
+    where
+        F: FnMut(&StepResult<M::Cell>) -> bool,
+    {
         let mut outputs = vec![];
-        let mut done = false;
         loop {
-            if self.needs_input() && self.num_inputs() == 0
-            {
-                break;
+            let step = self.execute()?;
+            if let StepResult::Output(output) = &step {
+                outputs.push(output.clone());
             }
+            match step {
+                StepResult::Halted | StepResult::NeedsInput => return Ok((outputs, step)),
+                _ if stop(&step) => return Ok((outputs, step)),
+                _ => {}
+            }
+        }
+    }
 
-            let (exit, output) = self.execute();
-            if exit {
-                done = true;
-                break;
+    pub fn run_until_outputs(&mut self, count: usize) -> RunResult<M::Cell> {
+        let mut seen = 0;
+        self.run_until(|step| {
+            if let StepResult::Output(_) = step {
+                seen += 1;
             }
-            if output.is_some() {
-                outputs.push(output.unwrap());
+            seen >= count
+        })
+    }
+
+    pub fn run_until_blocked_or_done(&mut self) -> RunResult<M::Cell> {
+        self.run_until(|_| false)
+    }
+
+    pub fn run(&mut self) -> Result<Vec<M::Cell>, IntcodeError> {
+        match self.run_until_blocked_or_done()? {
+            (_, StepResult::NeedsInput) => Err(IntcodeError::MissingInput {
+                ip: self.ip,
+                opcode: saturate(&self.val_at(self.ip)),
+            }),
+            (outputs, _) => Ok(outputs),
+        }
+    }
+
+    pub fn execute(&mut self) -> Result<StepResult<M::Cell>, IntcodeError> {
+        if self.watchdog.is_none() && self.guard.is_none() && self.history.is_none() {
+            return self.execute_observed();
+        }
+        self.execute_checked()
+    }
+
+    /// Checks the instruction against the limits and the access policy before running
+    /// it, so nothing they trap ever happens, then accounts for what it did and logs
+    /// how to undo it.
+    fn execute_checked(&mut self) -> Result<StepResult<M::Cell>, IntcodeError> {
+        let ip = self.ip;
+        let exceeded = |limit| IntcodeError::LimitExceeded { ip, limit };
+        let opcode = self.next_opcode()?;
+        let writes = (0..opcode.command.num_params())
+            .filter(|&offset| opcode.command.is_write(offset))
+            .map(|offset| self.target(&opcode, offset))
+            .collect::<Result<Vec<usize>, IntcodeError>>()?;
+        if let Some(watchdog) = &self.watchdog {
+            let halts = opcode.command == Command::STOP;
+            let outputs = opcode.command == Command::OUTPUT;
+            watchdog.check(halts, &writes, outputs).map_err(exceeded)?;
+        }
+        let reads = match self.guard {
+            Some(_) => self.read_addresses(&opcode),
+            None => vec![],
+        };
+        let jump = match self.guard {
+            Some(_) => self.jump_target(&opcode)?,
+            None => None,
+        };
+        if let Some(guard) = &mut self.guard {
+            guard.check_reads(ip, &reads)?;
+            guard.check_writes(ip, &writes)?;
+            if let Some(target) = jump {
+                guard.check_jump(ip, target)?;
             }
         }
-        (outputs, done)
+
+        let relative_base = self.relative_base;
+        let old_values = match self.history {
+            Some(_) => writes
+                .iter()
+                .map(|&address| (address, self.val_at(address)))
+                .collect(),
+            None => vec![],
+        };
+
+        let result = self.execute_observed()?;
+        if let StepResult::NeedsInput | StepResult::Halted = result {
+            return Ok(result);
+        }
+        let input = match opcode.command {
+            Command::INPUT if self.history.is_some() => Some(self.val_at(writes[0])),
+            _ => None,
+        };
+        if let Some(history) = &mut self.history {
+            let output = match &result {
+                StepResult::Output(output) => Some(output.clone()),
+                _ => None,
+            };
+            let change = Change {
+                ip,
+                relative_base,
+                writes: old_values,
+                input,
+                output,
+            };
+            history.push(change);
+        }
+        if let Some(guard) = &mut self.guard {
+            guard.record_writes(&writes);
+        }
+        if let Some(watchdog) = &mut self.watchdog {
+            let output = matches!(result, StepResult::Output(_));
+            watchdog.record(output).map_err(exceeded)?;
+        }
+        Ok(result)
     }
 
-    pub fn run(&mut self) -> Vec<i128> {
-        let mut outputs = vec![];
-        loop {
-            let (exit, output) = self.execute();
-            if exit {
-                break;
+    fn execute_observed(&mut self) -> Result<StepResult<M::Cell>, IntcodeError> {
+        if self.trace.is_none() && self.profile.is_none() {
+            return self.execute_instruction();
+        }
+        let ip = self.ip;
+        let opcode = self.next_opcode()?;
+        let params = self.get_params(&opcode)?;
+        let target = match opcode.command.write_param() {
+            Some(offset) => Some(self.target(&opcode, offset)?),
+            None => None,
+        };
+        let reads = match self.profile {
+            Some(_) => self.read_addresses(&opcode),
+            None => vec![],
+        };
+        let is_input = opcode.command == Command::INPUT;
+        if let Some(profile) = self.profile.as_mut().filter(|_| is_input) {
+            profile.input_started();
+        }
+
+        let result = self.execute_instruction()?;
+        if let Some(profile) = &mut self.profile {
+            if result == StepResult::NeedsInput {
+                profile.input_stalled();
+                return Ok(result);
+            }
+            if is_input {
+                profile.input_received();
             }
-            if output.is_some() {
-                outputs.push(output.unwrap());
+            let mnemonic = match (opcode.command, &self.registry) {
+                (Command::CUSTOM(custom), Some(registry)) => registry.mnemonic(custom.code),
+                (command, _) => command.mnemonic(),
+            };
+            profile.record_step(ip, mnemonic, &reads, target);
+            let jumped = match opcode.command {
+                Command::JIT => !params[0].is_zero(),
+                Command::JIF => params[0].is_zero(),
+                Command::CUSTOM(_) => self.ip != ip + opcode.modes().len() + 1,
+                _ => false,
+            };
+            if jumped {
+                profile.record_jump(ip, self.ip);
             }
         }
-        outputs
+        let tracer = match &self.trace {
+            Some(tracer) => tracer,
+            None => return Ok(result),
+        };
+        if result == StepResult::NeedsInput {
+            return Ok(result);
+        }
+        let write = target.map(|address| (address, self.val_at(address)));
+        tracer.record(&TraceEntry {
+            ip,
+            opcode: opcode.raw,
+            params,
+            input: match opcode.command {
+                Command::INPUT => write.as_ref().map(|(_, value)| value.clone()),
+                _ => None,
+            },
+            write,
+            output: match &result {
+                StepResult::Output(output) => Some(output.clone()),
+                _ => None,
+            },
+        });
+        Ok(result)
     }
 
-    pub fn execute(&mut self) -> (bool, Option<i128>) {
-        let opcode = self.next_opcode();
-        let params = self.get_params(&opcode);
+    fn execute_instruction(&mut self) -> Result<StepResult<M::Cell>, IntcodeError> {
+        let opcode = self.next_opcode()?;
         if self.debug {
+            let params = self.get_params(&opcode)?;
             println!(
                 "{}: Running command {:?} with params {:?}",
                 self.ip, opcode.command, params
@@ -265,130 +1116,251 @@ impl Program {
         }
         match opcode.command {
             Command::ADD => {
-                self.set(params[2] as usize, params[0] + params[1]);
+                let (a, b) = (self.param(&opcode, 0)?, self.param(&opcode, 1)?);
+                let sum = match self.arithmetic {
+                    Arithmetic::Checked => {
+                        a.checked_add(&b).ok_or_else(|| self.overflow(&opcode))?
+                    }
+                    Arithmetic::Wrapping => a.wrapping_add(&b),
+                };
+                self.set(self.target(&opcode, 2)?, sum);
                 self.ip += OPER_NUM_PARAMS + 1;
-                (false, None)
+                Ok(StepResult::Continued)
             }
             Command::MULTIPLY => {
-                self.set(params[2] as usize, params[0] * params[1]);
+                let (a, b) = (self.param(&opcode, 0)?, self.param(&opcode, 1)?);
+                let product = match self.arithmetic {
+                    Arithmetic::Checked => {
+                        a.checked_mul(&b).ok_or_else(|| self.overflow(&opcode))?
+                    }
+                    Arithmetic::Wrapping => a.wrapping_mul(&b),
+                };
+                self.set(self.target(&opcode, 2)?, product);
                 self.ip += OPER_NUM_PARAMS + 1;
-                (false, None)
+                Ok(StepResult::Continued)
             }
             Command::INPUT => {
-                if self.input_index >= self.inputs.borrow().len() {
-                    panic!("Need an input but could not find one");
-                }
+                let address = self.target(&opcode, 0)?;
+                let replayed = self.history.as_mut().and_then(|h| h.replayed_input());
+                let input = match replayed.or_else(|| self.input.next_input()) {
+                    Some(input) => input,
+                    None => return Ok(StepResult::NeedsInput),
+                };
                 if self.debug {
-                    println!("\tInput is {}", self.inputs.borrow()[self.input_index]);
+                    println!("\tInput is {}", input);
                 }
-                self.set(params[0] as usize, self.inputs.borrow()[self.input_index]);
-                self.input_index += 1;
+                self.set(address, input);
                 self.ip += IO_NUM_PARAMS + 1;
-                (false, None)
+                Ok(StepResult::Continued)
             }
             Command::OUTPUT => {
+                let value = self.param(&opcode, 0)?;
+                self.output.send_output(value.clone());
                 self.ip += IO_NUM_PARAMS + 1;
-                (false, Some(params[0]))
+                Ok(StepResult::Output(value))
             }
             Command::JIT => {
-                if params[0] != 0 {
-                    self.ip = params[1] as usize;
+                let (condition, jump) = (self.param(&opcode, 0)?, self.param(&opcode, 1)?);
+                if !condition.is_zero() {
+                    self.ip = self.cell_address(&opcode, &jump, 0)?;
                 } else {
                     self.ip += JUMP_NUM_PARAMS + 1;
                 }
-                (false, None)
+                Ok(StepResult::Continued)
             }
             Command::JIF => {
-                if params[0] == 0 {
-                    self.ip = params[1] as usize;
+                let (condition, jump) = (self.param(&opcode, 0)?, self.param(&opcode, 1)?);
+                if condition.is_zero() {
+                    self.ip = self.cell_address(&opcode, &jump, 0)?;
                 } else {
                     self.ip += JUMP_NUM_PARAMS + 1;
                 }
-                (false, None)
+                Ok(StepResult::Continued)
             }
             Command::LESS => {
-                if params[0] < params[1] {
-                    self.set(params[2] as usize, 1);
-                } else {
-                    self.set(params[2] as usize, 0);
-                }
+                let (a, b) = (self.param(&opcode, 0)?, self.param(&opcode, 1)?);
+                self.set(self.target(&opcode, 2)?, M::Cell::from_bool(a < b));
                 self.ip += CMP_NUM_PARAMS + 1;
-                (false, None)
+                Ok(StepResult::Continued)
             }
             Command::EQUALS => {
-                if params[0] == params[1] {
-                    self.set(params[2] as usize, 1);
-                } else {
-                    self.set(params[2] as usize, 0);
-                }
+                let (a, b) = (self.param(&opcode, 0)?, self.param(&opcode, 1)?);
+                self.set(self.target(&opcode, 2)?, M::Cell::from_bool(a == b));
                 self.ip += CMP_NUM_PARAMS + 1;
-                (false, None)
+                Ok(StepResult::Continued)
             }
             Command::REL => {
-                self.relative_base = (self.relative_base as i128 + params[0]) as usize;
+                let offset = self.param(&opcode, 0)?;
+                self.relative_base = offset
+                    .to_i128()
+                    .and_then(|offset| self.relative_base.checked_add(offset))
+                    .ok_or_else(|| self.overflow(&opcode))?;
                 if self.debug {
                     println!("\tRel base set to {}", self.relative_base);
                 }
                 self.ip += REL_NUM_PARAMS + 1;
-                (false, None)
+                Ok(StepResult::Continued)
             }
-            Command::STOP => (true, None),
+            Command::STOP => Ok(StepResult::Halted),
+            Command::CUSTOM(custom) => self.execute_custom(&opcode, custom),
         }
     }
+
+    fn execute_custom(
+        &mut self,
+        opcode: &Opcode,
+        custom: CustomCommand,
+    ) -> Result<StepResult<M::Cell>, IntcodeError> {
+        let registry = match &self.registry {
+            Some(registry) => registry.clone(),
+            None => {
+                return Err(IntcodeError::InvalidOpcode {
+                    ip: self.ip,
+                    opcode: opcode.raw,
+                })
+            }
+        };
+        let args = self.get_params(opcode)?;
+        let called = registry.call(custom.code as i128, self.ip, &args);
+        let (action, writes) = called.map_err(|message| IntcodeError::Extension {
+            ip: self.ip,
+            opcode: opcode.raw,
+            message,
+        })?;
+        let next = self.ip + opcode.modes().len() + 1;
+        // The handler only picks its target as it runs, so this is the earliest the
+        // access policy can see the jump, but still before anything is written.
+        if let (Action::Jump(target), Some(guard)) = (&action, &mut self.guard) {
+            if *target != next {
+                guard.check_jump(self.ip, *target)?;
+            }
+        }
+        for (offset, value) in writes {
+            self.set(self.target(opcode, offset)?, value);
+        }
+        match action {
+            Action::Continue => {
+                self.ip = next;
+                Ok(StepResult::Continued)
+            }
+            Action::Output(value) => {
+                self.output.send_output(value.clone());
+                self.ip = next;
+                Ok(StepResult::Output(value))
+            }
+            Action::Jump(address) => {
+                self.ip = address;
+                Ok(StepResult::Continued)
+            }
+            Action::Halt => Ok(StepResult::Halted),
+            Action::Exit(code) => {
+                self.exit_code = Some(code);
+                Ok(StepResult::Halted)
+            }
+        }
+    }
+}
+
+/// Decodes every address of the loaded program once, shared by all clones of it.
+/// Words that aren't valid opcodes (data, mostly) are left empty.
+fn predecode<M: Memory>(
+    memory: &M,
+    registry: Option<&OpcodeRegistry<M::Cell>>,
+) -> Arc<[Option<Opcode>]> {
+    let custom = |code| registry?.command(code);
+    (0..memory.len())
+        .map(|address| Opcode::from_cell(&memory.get(address), address, custom).ok())
+        .collect()
 }
 
-pub fn process_program(program_string: &str, inputs: &Vec<i128>) -> (Program, Vec<i128>) {
-    let mut program = Program::new(program_string, inputs);
-    let outputs = program.run();
-    (program, outputs)
+pub fn process_program(
+    program_string: &str,
+    inputs: &[i128],
+) -> Result<(Program, Vec<i128>), IntcodeError> {
+    let mut program = Program::new(program_string, inputs)?;
+    let outputs = program.run()?;
+    Ok((program, outputs))
 }
 
 pub struct Computer {
     program: Program,
-    saved_program_str: String,
+    saved_program: Program,
     pub saved_output: Option<i128>,
 }
 impl Computer {
-    pub fn new(program_str: &str) -> Self {
-        Computer {
-            program: Program::new(program_str, &vec![]),
-            saved_program_str: String::from(program_str),
-            saved_output: None
-        }
+    pub fn new(program_str: &str) -> Result<Self, IntcodeError> {
+        let program = Program::new(program_str, &[])?;
+        Ok(Computer {
+            saved_program: program.clone(),
+            program,
+            saved_output: None,
+        })
     }
 
     pub fn reset(&mut self) {
-        self.program = Program::new(&self.saved_program_str[..], &vec![]);
+        self.program = self.saved_program.clone();
     }
 
     pub fn send_ascii(&mut self, ascii: &str) {
         ascii.chars().for_each(|c| self.program.send_input(c as i128))
     }
 
-    pub fn run_until_blocked_or_done(&mut self) -> (String, bool) {
-        let (mut outputs, done) = self.program.run_until_blocked_or_done();
+    pub fn run_until_blocked_or_done(&mut self) -> Result<(String, StepResult), IntcodeError> {
+        let (mut outputs, state) = self.program.run_until_blocked_or_done()?;
         let num_outputs = outputs.len();
-        if outputs.len() > 0 && outputs[outputs.len() - 1] > u8::max_value() as i128 {
+        if !outputs.is_empty() && outputs[num_outputs - 1] > u8::MAX as i128 {
             self.saved_output = Some(outputs[num_outputs - 1]);
-            outputs = outputs.into_iter().take(num_outputs - 1).collect();
+            outputs.truncate(num_outputs - 1);
         }
         let output_str = outputs.into_iter().map(|i| i as u8 as char).collect::<String>();
         print!("{}", output_str);
-        (output_str, done)
+        Ok((output_str, state))
     }
 
-    pub fn run_interactive(&mut self) {
+    pub fn run_interactive(&mut self) -> Result<(), IntcodeError> {
         loop {
-            let (display_str, done) = self.run_until_blocked_or_done();
+            let (_display_str, state) = self.run_until_blocked_or_done()?;
             if let Some(val) = self.saved_output {
                 println!("Output is {}", val)
             }
-            if done {
+            if state.is_halted() {
                 break;
             }
             let mut input = String::new();
-            io::stdin().read_line(&mut input).unwrap();
-            self.send_ascii(&input[..]);
+            std::io::stdin().read_line(&mut input).unwrap();
+            if !self.session_command(input.trim()) {
+                self.send_ascii(&input[..]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the running program, queued input included, so `load` can resume it later.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), IntcodeError> {
+        self.program.save_state(path)
+    }
+
+    /// Replaces the running program with one written by `save`. `reset` still goes back
+    /// to the original program.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), IntcodeError> {
+        self.program = Program::load_state(path)?;
+        Ok(())
+    }
+
+    /// Handles `save <path>` and `load <path>` typed at the prompt. Returns false for
+    /// lines meant for the program.
+    fn session_command(&mut self, line: &str) -> bool {
+        let (result, done) = if let Some(path) = line.strip_prefix("save ") {
+            (self.save(path.trim()), "Saved")
+        } else if let Some(path) = line.strip_prefix("load ") {
+            (self.load(path.trim()), "Loaded")
+        } else {
+            return false;
+        };
+        match result {
+            Ok(()) => println!("{} {}", done, line[5..].trim()),
+            Err(e) => println!("{}", e),
         }
+        true
     }
 }
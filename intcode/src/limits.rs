@@ -0,0 +1,98 @@
+use std::fmt;
+
+/// Caps on what a program may do before it's stopped with
+/// `IntcodeError::LimitExceeded`. Unset caps don't apply.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Instructions to execute in total.
+    pub max_steps: Option<u64>,
+    /// Writes must land below this address.
+    pub max_memory: Option<usize>,
+    /// Values to output in total.
+    pub max_outputs: Option<usize>,
+}
+impl Limits {
+    pub fn new() -> Self {
+        Limits::default()
+    }
+
+    pub fn steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    pub fn memory(mut self, max_memory: usize) -> Self {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    pub fn outputs(mut self, max_outputs: usize) -> Self {
+        self.max_outputs = Some(max_outputs);
+        self
+    }
+}
+
+/// Which of the `Limits` a program ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Steps(u64),
+    /// The address the program tried to write to.
+    Memory(usize),
+    Outputs(usize),
+}
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Steps(steps) => write!(f, "Ran out of steps after {}", steps),
+            Limit::Memory(address) => write!(f, "Write to {} is past the memory limit", address),
+            Limit::Outputs(outputs) => write!(f, "Output limit of {} reached", outputs),
+        }
+    }
+}
+
+/// The limits a program runs under and how much of them it has used.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Watchdog {
+    pub limits: Limits,
+    pub steps: u64,
+    pub outputs: usize,
+}
+impl Watchdog {
+    pub fn new(limits: Limits) -> Self {
+        Watchdog {
+            limits,
+            steps: 0,
+            outputs: 0,
+        }
+    }
+
+    /// Checks an instruction before it runs: whether it may run at all, the addresses
+    /// it will write and whether it will output.
+    pub fn check(&self, halts: bool, writes: &[usize], outputs: bool) -> Result<(), Limit> {
+        match self.limits.max_steps {
+            Some(max) if !halts && self.steps >= max => return Err(Limit::Steps(max)),
+            _ => {}
+        }
+        if let Some(max) = self.limits.max_memory {
+            if let Some(&address) = writes.iter().find(|&&address| address >= max) {
+                return Err(Limit::Memory(address));
+            }
+        }
+        self.check_outputs(outputs as usize)
+    }
+
+    /// Counts an instruction that ran. A custom opcode can output without `check`
+    /// knowing it would, so the output count is checked again.
+    pub fn record(&mut self, output: bool) -> Result<(), Limit> {
+        self.steps += 1;
+        self.outputs += output as usize;
+        self.check_outputs(0)
+    }
+
+    fn check_outputs(&self, pending: usize) -> Result<(), Limit> {
+        match self.limits.max_outputs {
+            Some(max) if self.outputs + pending > max => Err(Limit::Outputs(max)),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::Cell;
+
+/// Storage for a program's cells. Addresses that were never written read as 0.
+pub trait Memory {
+    type Cell: Cell;
+
+    fn from_cells(cells: Vec<Self::Cell>) -> Self;
+
+    fn get(&self, address: usize) -> Self::Cell;
+
+    fn set(&mut self, address: usize, value: Self::Cell);
+
+    /// One past the end of the cells kept contiguously. Far writes past it are
+    /// listed by `sparse_cells` instead.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The cells below `len`.
+    fn to_vec(&self) -> Vec<Self::Cell> {
+        (0..self.len()).map(|address| self.get(address)).collect()
+    }
+
+    /// The nonzero cells at or past `len`, by address.
+    fn sparse_cells(&self) -> Vec<(usize, Self::Cell)> {
+        vec![]
+    }
+}
+
+/// The nonzero cells of `cells`, by address.
+fn nonzero<C: Cell>(cells: impl Iterator<Item = (usize, C)>) -> Vec<(usize, C)> {
+    let mut cells = cells
+        .filter(|(_, value)| *value != C::default())
+        .collect::<Vec<_>>();
+    cells.sort_by_key(|&(address, _)| address);
+    cells
+}
+
+/// Writes below this address grow contiguous storage; writes past it are kept
+/// sparsely, so a single far write can't allocate memory for every cell before it.
+const DENSE_LIMIT: usize = 1 << 20;
+
+/// Contiguous cells, grown with zeros when a write lands past the end. Writes past
+/// `DENSE_LIMIT` go to a sparse overflow area instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VecMemory<C = i128> {
+    cells: Vec<C>,
+    /// Every address here is at or past the end of `cells`.
+    overflow: HashMap<usize, C>,
+}
+impl<C: Cell> Memory for VecMemory<C> {
+    type Cell = C;
+
+    fn from_cells(cells: Vec<C>) -> Self {
+        VecMemory {
+            cells,
+            overflow: HashMap::new(),
+        }
+    }
+
+    fn get(&self, address: usize) -> C {
+        match self.cells.get(address) {
+            Some(val) => val.clone(),
+            None => self.overflow.get(&address).cloned().unwrap_or_default(),
+        }
+    }
+
+    fn set(&mut self, address: usize, value: C) {
+        if address < self.cells.len() {
+            self.cells[address] = value;
+        } else if address < DENSE_LIMIT {
+            self.cells.resize(address + 1, C::default());
+            self.cells[address] = value;
+        } else {
+            self.overflow.insert(address, value);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn to_vec(&self) -> Vec<C> {
+        self.cells.clone()
+    }
+
+    fn sparse_cells(&self) -> Vec<(usize, C)> {
+        nonzero(
+            self.overflow
+                .iter()
+                .map(|(&address, value)| (address, value.clone())),
+        )
+    }
+}
+
+/// Sparse cells keyed by address, for programs that write far past their own length.
+/// Cells past `DENSE_LIMIT` count as sparse ones, as they do for the other backends.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HashMemory<C = i128> {
+    cells: HashMap<usize, C>,
+}
+impl<C: Cell> Memory for HashMemory<C> {
+    type Cell = C;
+
+    fn from_cells(cells: Vec<C>) -> Self {
+        HashMemory {
+            cells: cells.into_iter().enumerate().collect(),
+        }
+    }
+
+    fn get(&self, address: usize) -> C {
+        match self.cells.get(&address) {
+            Some(val) => val.clone(),
+            None => C::default(),
+        }
+    }
+
+    fn set(&mut self, address: usize, value: C) {
+        self.cells.insert(address, value);
+    }
+
+    fn len(&self) -> usize {
+        self.cells
+            .keys()
+            .filter(|&&address| address < DENSE_LIMIT)
+            .max()
+            .map_or(0, |&address| address + 1)
+    }
+
+    fn sparse_cells(&self) -> Vec<(usize, C)> {
+        let far = self
+            .cells
+            .iter()
+            .filter(|(&address, _)| address >= DENSE_LIMIT);
+        nonzero(far.map(|(&address, value)| (address, value.clone())))
+    }
+}
+
+const PAGE_BITS: usize = 8;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+const PAGE_MASK: usize = PAGE_SIZE - 1;
+
+type Page<C> = [C; PAGE_SIZE];
+
+/// Fixed-size pages shared between clones and copied on first write, so cloning
+/// a program costs one pointer per page and each clone only pays for what it changes.
+/// Pages past `DENSE_LIMIT` are looked up by number rather than kept in the table.
+#[derive(Debug, Clone)]
+pub struct PagedMemory<C = i128> {
+    pages: Vec<Option<Arc<Page<C>>>>,
+    far_pages: HashMap<usize, Arc<Page<C>>>,
+    len: usize,
+}
+impl<C> Default for PagedMemory<C> {
+    fn default() -> Self {
+        PagedMemory {
+            pages: vec![],
+            far_pages: HashMap::new(),
+            len: 0,
+        }
+    }
+}
+impl<C> PagedMemory<C> {
+    /// Number of pages this memory and `other` still share without copying.
+    pub fn shared_pages(&self, other: &PagedMemory<C>) -> usize {
+        let near = self
+            .pages
+            .iter()
+            .zip(other.pages.iter())
+            .filter(|(a, b)| match (a, b) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                _ => false,
+            })
+            .count();
+        let far = self
+            .far_pages
+            .iter()
+            .filter(|(index, a)| {
+                other
+                    .far_pages
+                    .get(index)
+                    .is_some_and(|b| Arc::ptr_eq(a, b))
+            })
+            .count();
+        near + far
+    }
+
+    fn page(&self, index: usize) -> Option<&Arc<Page<C>>> {
+        match self.pages.get(index) {
+            Some(page) => page.as_ref(),
+            None => self.far_pages.get(&index),
+        }
+    }
+}
+impl<C: Cell> Memory for PagedMemory<C> {
+    type Cell = C;
+
+    fn from_cells(cells: Vec<C>) -> Self {
+        let mut memory = PagedMemory::default();
+        for (address, value) in cells.into_iter().enumerate() {
+            memory.set(address, value);
+        }
+        memory
+    }
+
+    fn get(&self, address: usize) -> C {
+        match self.page(address >> PAGE_BITS) {
+            Some(page) => page[address & PAGE_MASK].clone(),
+            None => C::default(),
+        }
+    }
+
+    fn set(&mut self, address: usize, value: C) {
+        let index = address >> PAGE_BITS;
+        let new_page = || Arc::new(std::array::from_fn(|_| C::default()));
+        let page = if address < DENSE_LIMIT {
+            if index >= self.pages.len() {
+                self.pages.resize(index + 1, None);
+            }
+            self.pages[index].get_or_insert_with(new_page)
+        } else {
+            self.far_pages.entry(index).or_insert_with(new_page)
+        };
+        Arc::make_mut(page)[address & PAGE_MASK] = value;
+        if address < DENSE_LIMIT {
+            self.len = self.len.max(address + 1);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn sparse_cells(&self) -> Vec<(usize, C)> {
+        let far = self.far_pages.iter().flat_map(|(&index, page)| {
+            let start = index << PAGE_BITS;
+            page.iter()
+                .enumerate()
+                .map(move |(offset, value)| (start + offset, value.clone()))
+        });
+        nonzero(far)
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+
+use crate::{Cell, IntcodeError, Memory, Program, StepResult, VecMemory};
+
+/// Empty polls in a row after which a machine reading `Network::with_idle_input`
+/// values counts as idle.
+pub(crate) const IDLE_POLLS: usize = 2;
+
+/// Values sent from one machine to an address. Addresses past the last machine
+/// don't belong to the network and come back out of `Network::next_event`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message<C = i128> {
+    pub from: usize,
+    pub to: usize,
+    pub values: Vec<C>,
+}
+
+/// Decides where outputs go. Gets every output value in the order the machines produce
+/// them and returns a message once it knows where to send one.
+pub trait Router<C> {
+    fn route(&mut self, from: usize, value: C, machines: usize) -> Option<Message<C>>;
+}
+impl<C, F> Router<C> for F
+where
+    F: FnMut(usize, C, usize) -> Option<Message<C>>,
+{
+    fn route(&mut self, from: usize, value: C, machines: usize) -> Option<Message<C>> {
+        self(from, value, machines)
+    }
+}
+
+/// Each machine feeds the next; the last machine's outputs leave the network.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pipeline;
+impl<C> Router<C> for Pipeline {
+    fn route(&mut self, from: usize, value: C, _machines: usize) -> Option<Message<C>> {
+        Some(Message {
+            from,
+            to: from + 1,
+            values: vec![value],
+        })
+    }
+}
+
+/// Each machine feeds the next, and the last feeds the first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ring;
+impl<C> Router<C> for Ring {
+    fn route(&mut self, from: usize, value: C, machines: usize) -> Option<Message<C>> {
+        Some(Message {
+            from,
+            to: (from + 1) % machines,
+            values: vec![value],
+        })
+    }
+}
+
+/// Outputs are grouped into packets of `size` values: a destination address followed
+/// by the payload. An address that isn't a valid index becomes `usize::MAX`.
+#[derive(Debug, Clone)]
+pub struct Packets<C = i128> {
+    size: usize,
+    pending: Vec<Vec<C>>,
+}
+impl<C> Packets<C> {
+    /// Panics if `size` is 0, as every packet needs room for its address.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "packet size must be at least 1, got {}", size);
+        Packets {
+            size,
+            pending: vec![],
+        }
+    }
+}
+impl<C: Cell> Router<C> for Packets<C> {
+    fn route(&mut self, from: usize, value: C, machines: usize) -> Option<Message<C>> {
+        if self.pending.len() < machines {
+            self.pending.resize_with(machines, Vec::new);
+        }
+        let pending = &mut self.pending[from];
+        pending.push(value);
+        if pending.len() < self.size {
+            return None;
+        }
+        let mut values = pending.split_off(0);
+        let address = values.remove(0);
+        let to = address
+            .to_i128()
+            .and_then(|address| usize::try_from(address).ok())
+            .unwrap_or(usize::MAX);
+        Some(Message { from, to, values })
+    }
+}
+
+/// Why `Network::next_event` handed control back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<C = i128> {
+    /// A message addressed outside the network.
+    Message(Message<C>),
+    /// Every machine that hasn't halted is waiting for input and nothing is in flight.
+    /// Running on without sending anything reports idle again.
+    Idle,
+    /// Every machine has halted.
+    Halted,
+}
+
+/// Runs a set of programs that talk to each other. Machines take turns executing one
+/// instruction each, always in the same order, so a network behaves the same every run.
+pub struct Network<R, M: Memory = VecMemory> {
+    machines: Vec<Program<VecDeque<M::Cell>, (), M>>,
+    router: R,
+    idle_input: Option<M::Cell>,
+    halted: Vec<bool>,
+    waiting: Vec<bool>,
+    polls: Vec<usize>,
+    last_outputs: Vec<Option<M::Cell>>,
+    /// The machine to run next within the current round.
+    cursor: usize,
+}
+impl<R: Router<M::Cell>, M: Memory> Network<R, M> {
+    pub fn new(machines: Vec<Program<VecDeque<M::Cell>, (), M>>, router: R) -> Self {
+        let count = machines.len();
+        Network {
+            machines,
+            router,
+            idle_input: None,
+            halted: vec![false; count],
+            waiting: vec![false; count],
+            polls: vec![0; count],
+            last_outputs: vec![None; count],
+            cursor: 0,
+        }
+    }
+
+    /// Feeds `value` to a machine that reads with nothing queued, instead of letting it
+    /// block, like the -1 of a non-blocking network card. A machine counts as idle once
+    /// it has polled an empty queue twice in a row.
+    pub fn with_idle_input(mut self, value: M::Cell) -> Self {
+        self.idle_input = Some(value);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    pub fn machine(&self, index: usize) -> &Program<VecDeque<M::Cell>, (), M> {
+        &self.machines[index]
+    }
+
+    pub fn machine_mut(&mut self, index: usize) -> &mut Program<VecDeque<M::Cell>, (), M> {
+        &mut self.machines[index]
+    }
+
+    /// The most recent value machine `index` output, wherever it was routed.
+    pub fn last_output(&self, index: usize) -> Option<&M::Cell> {
+        self.last_outputs[index].as_ref()
+    }
+
+    /// Queues `values` on machine `to`'s input, e.g. from a NAT handling `Event::Idle`.
+    pub fn send(&mut self, to: usize, values: &[M::Cell]) {
+        for value in values {
+            self.machines[to].send_input(value.clone());
+        }
+        self.polls[to] = 0;
+    }
+
+    /// Runs until a message leaves the network, the network goes idle or everything halts.
+    pub fn next_event(&mut self) -> Result<Event<M::Cell>, IntcodeError> {
+        loop {
+            if self.cursor == self.machines.len() {
+                self.cursor = 0;
+                if self.halted.iter().all(|&halted| halted) {
+                    return Ok(Event::Halted);
+                }
+                if self.is_idle() {
+                    return Ok(Event::Idle);
+                }
+            }
+            let index = self.cursor;
+            self.cursor += 1;
+            if let Some(message) = self.run_machine(index)? {
+                if message.to < self.machines.len() {
+                    self.send(message.to, &message.values);
+                } else {
+                    return Ok(Event::Message(message));
+                }
+            }
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        (0..self.machines.len()).all(|index| {
+            self.halted[index]
+                || (self.machines[index].num_inputs() == 0
+                    && (self.waiting[index] || self.polls[index] >= IDLE_POLLS))
+        })
+    }
+
+    /// Executes one instruction of machine `index` and returns anything it routed.
+    fn run_machine(&mut self, index: usize) -> Result<Option<Message<M::Cell>>, IntcodeError> {
+        let machine = &mut self.machines[index];
+        self.waiting[index] = false;
+        if self.halted[index] {
+            return Ok(None);
+        }
+        if machine.needs_input() {
+            if machine.num_inputs() > 0 {
+                self.polls[index] = 0;
+            } else if let Some(value) = &self.idle_input {
+                machine.send_input(value.clone());
+                self.polls[index] += 1;
+            } else {
+                self.waiting[index] = true;
+                return Ok(None);
+            }
+        }
+        match machine.execute()? {
+            StepResult::Output(value) => {
+                self.last_outputs[index] = Some(value.clone());
+                let machines = self.machines.len();
+                Ok(self.router.route(index, value, machines))
+            }
+            StepResult::Halted => {
+                self.halted[index] = true;
+                Ok(None)
+            }
+            StepResult::NeedsInput | StepResult::Continued => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::Disassembly;
+
+/// What to do when a program makes an access an `AccessPolicy` watches for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Policy {
+    #[default]
+    Allow,
+    /// Carry on, but record a `Violation` in `Program::violations`.
+    Warn,
+    /// Stop with `IntcodeError::AccessViolation` before the access happens.
+    Trap,
+}
+
+/// Accesses that are legal intcode but usually mean a bug. Negative addresses are
+/// always an error, whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AccessPolicy {
+    /// Reads past the loaded program of cells nothing has written.
+    pub uninitialized_read: Policy,
+    /// Writes into instructions the disassembler can reach from address 0.
+    pub code_write: Policy,
+    /// Jumps to anything other than those instructions.
+    pub data_jump: Policy,
+}
+impl AccessPolicy {
+    pub fn new() -> Self {
+        AccessPolicy::default()
+    }
+
+    /// The same policy for every kind of access.
+    pub fn all(policy: Policy) -> Self {
+        AccessPolicy {
+            uninitialized_read: policy,
+            code_write: policy,
+            data_jump: policy,
+        }
+    }
+
+    pub fn uninitialized_reads(mut self, policy: Policy) -> Self {
+        self.uninitialized_read = policy;
+        self
+    }
+
+    pub fn code_writes(mut self, policy: Policy) -> Self {
+        self.code_write = policy;
+        self
+    }
+
+    pub fn data_jumps(mut self, policy: Policy) -> Self {
+        self.data_jump = policy;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Access {
+    UninitializedRead,
+    CodeWrite,
+    DataJump,
+}
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::UninitializedRead => write!(f, "Read of uninitialized memory"),
+            Access::CodeWrite => write!(f, "Write into code"),
+            Access::DataJump => write!(f, "Jump into data"),
+        }
+    }
+}
+
+/// An access the instruction at `ip` made to `address`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Violation {
+    pub access: Access,
+    pub ip: usize,
+    pub address: usize,
+}
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} at {}", self.ip, self.access, self.address)
+    }
+}
+
+/// Enforces an `AccessPolicy` for one program.
+#[derive(Debug, Clone)]
+pub(crate) struct Guard {
+    policy: AccessPolicy,
+    /// Which words of the loaded program belong to reachable instructions.
+    code: Vec<bool>,
+    /// Addresses past the loaded program that have been written.
+    written: HashSet<usize>,
+    /// Each distinct violation once, so a loop or a blocked input retrying the same
+    /// instruction doesn't report it over and over.
+    violations: Vec<Violation>,
+    reported: HashSet<Violation>,
+}
+impl Guard {
+    pub fn new(policy: AccessPolicy, words: Vec<i128>) -> Self {
+        let disassembly = Disassembly::new(words);
+        let code = (0..disassembly.words().len())
+            .map(|address| disassembly.is_code(address))
+            .collect();
+        Guard {
+            policy,
+            code,
+            written: HashSet::new(),
+            violations: vec![],
+            reported: HashSet::new(),
+        }
+    }
+
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    pub fn check_reads(&mut self, ip: usize, reads: &[usize]) -> Result<(), Violation> {
+        for &address in reads {
+            if address >= self.code.len() && !self.written.contains(&address) {
+                let policy = self.policy.uninitialized_read;
+                self.report(policy, Access::UninitializedRead, ip, address)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_writes(&mut self, ip: usize, writes: &[usize]) -> Result<(), Violation> {
+        for &address in writes {
+            if self.code.get(address) == Some(&true) {
+                self.report(self.policy.code_write, Access::CodeWrite, ip, address)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn record_writes(&mut self, writes: &[usize]) {
+        let loaded = self.code.len();
+        self.written
+            .extend(writes.iter().filter(|&&address| address >= loaded));
+    }
+
+    pub fn check_jump(&mut self, ip: usize, target: usize) -> Result<(), Violation> {
+        if self.code.get(target) != Some(&true) {
+            self.report(self.policy.data_jump, Access::DataJump, ip, target)?;
+        }
+        Ok(())
+    }
+
+    fn report(
+        &mut self,
+        policy: Policy,
+        access: Access,
+        ip: usize,
+        address: usize,
+    ) -> Result<(), Violation> {
+        let violation = Violation {
+            access,
+            ip,
+            address,
+        };
+        match policy {
+            Policy::Allow => Ok(()),
+            Policy::Warn => {
+                if self.reported.insert(violation) {
+                    self.violations.push(violation);
+                }
+                Ok(())
+            }
+            Policy::Trap => Err(violation),
+        }
+    }
+}
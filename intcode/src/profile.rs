@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// A loop found through a taken backward jump from `tail` to `head`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Loop {
+    pub head: usize,
+    pub tail: usize,
+    pub iterations: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryTouches {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+/// Counts collected by `Program::with_profiler`. Memory reads only count operands
+/// that go through memory, not instruction fetches or immediates.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    instructions: u64,
+    executions: HashMap<usize, (&'static str, u64)>,
+    opcodes: BTreeMap<&'static str, u64>,
+    memory: HashMap<usize, MemoryTouches>,
+    back_edges: HashMap<(usize, usize), u64>,
+    input_stalls: u64,
+    input_wait: Duration,
+    waiting_since: Option<Instant>,
+}
+impl Profile {
+    pub fn new() -> Self {
+        Profile::default()
+    }
+
+    pub(crate) fn record_step(
+        &mut self,
+        ip: usize,
+        mnemonic: &'static str,
+        reads: &[usize],
+        write: Option<usize>,
+    ) {
+        self.instructions += 1;
+        self.executions.entry(ip).or_insert((mnemonic, 0)).1 += 1;
+        *self.opcodes.entry(mnemonic).or_insert(0) += 1;
+        for &address in reads.iter() {
+            self.memory.entry(address).or_default().reads += 1;
+        }
+        if let Some(address) = write {
+            self.memory.entry(address).or_default().writes += 1;
+        }
+    }
+
+    pub(crate) fn record_jump(&mut self, from: usize, to: usize) {
+        if to <= from {
+            *self.back_edges.entry((from, to)).or_insert(0) += 1;
+        }
+    }
+
+    /// Marks the start of a wait for input, unless one is already running.
+    pub(crate) fn input_started(&mut self) {
+        self.waiting_since.get_or_insert_with(Instant::now);
+    }
+
+    pub(crate) fn input_stalled(&mut self) {
+        self.input_stalls += 1;
+    }
+
+    pub(crate) fn input_received(&mut self) {
+        if let Some(start) = self.waiting_since.take() {
+            self.input_wait += start.elapsed();
+        }
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn executions(&self, address: usize) -> u64 {
+        self.executions.get(&address).map_or(0, |&(_, count)| count)
+    }
+
+    pub fn opcode_count(&self, mnemonic: &str) -> u64 {
+        self.opcodes.get(mnemonic).cloned().unwrap_or(0)
+    }
+
+    pub fn memory_touches(&self, address: usize) -> MemoryTouches {
+        self.memory.get(&address).cloned().unwrap_or_default()
+    }
+
+    /// Times an input instruction found nothing to read and the program had to wait.
+    pub fn input_stalls(&self) -> u64 {
+        self.input_stalls
+    }
+
+    /// Wall time from an input instruction first running to it getting its value,
+    /// including time the caller took to send it after a stall.
+    pub fn input_wait(&self) -> Duration {
+        self.input_wait
+    }
+
+    /// The `count` most executed addresses, busiest first.
+    pub fn hot_spots(&self, count: usize) -> Vec<(usize, &'static str, u64)> {
+        let mut spots = self
+            .executions
+            .iter()
+            .map(|(&address, &(mnemonic, executions))| (address, mnemonic, executions))
+            .collect::<Vec<_>>();
+        spots.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        spots.truncate(count);
+        spots
+    }
+
+    /// The `count` most touched addresses, busiest first.
+    pub fn hot_memory(&self, count: usize) -> Vec<(usize, MemoryTouches)> {
+        let mut touched = self
+            .memory
+            .iter()
+            .map(|(&address, &touches)| (address, touches))
+            .collect::<Vec<_>>();
+        touched.sort_by(|a, b| {
+            let total = |touches: &MemoryTouches| touches.reads + touches.writes;
+            total(&b.1).cmp(&total(&a.1)).then(a.0.cmp(&b.0))
+        });
+        touched.truncate(count);
+        touched
+    }
+
+    /// Every back-edge taken at least once, most iterations first.
+    pub fn loops(&self) -> Vec<Loop> {
+        let mut loops = self
+            .back_edges
+            .iter()
+            .map(|(&(tail, head), &iterations)| Loop {
+                head,
+                tail,
+                iterations,
+            })
+            .collect::<Vec<Loop>>();
+        loops.sort_by(|a, b| {
+            b.iterations
+                .cmp(&a.iterations)
+                .then(a.head.cmp(&b.head))
+                .then(a.tail.cmp(&b.tail))
+        });
+        loops
+    }
+
+    /// A plain text report listing the top `count` entries of each table.
+    pub fn report(&self, count: usize) -> String {
+        let mut report = String::new();
+        let percent = |n: u64| 100.0 * n as f64 / self.instructions.max(1) as f64;
+        let _ = writeln!(report, "Instructions: {}", self.instructions);
+        let _ = writeln!(
+            report,
+            "Input: {} stalls, {:.3}s waiting",
+            self.input_stalls,
+            self.input_wait.as_secs_f64()
+        );
+
+        let _ = writeln!(report, "\nOpcodes:");
+        let mut opcodes = self.opcodes.iter().collect::<Vec<_>>();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1));
+        for (mnemonic, &executions) in opcodes {
+            let _ = writeln!(
+                report,
+                "  {:<6} {:>12} {:>6.2}%",
+                mnemonic,
+                executions,
+                percent(executions)
+            );
+        }
+
+        let _ = writeln!(report, "\nHot spots:");
+        for (address, mnemonic, executions) in self.hot_spots(count) {
+            let _ = writeln!(
+                report,
+                "  {:>6}: {:<6} {:>12} {:>6.2}%",
+                address,
+                mnemonic,
+                executions,
+                percent(executions)
+            );
+        }
+
+        let _ = writeln!(report, "\nLoops:");
+        for l in self.loops().into_iter().take(count) {
+            let _ = writeln!(
+                report,
+                "  {:>6} <- {:<6} {:>12} iterations",
+                l.head, l.tail, l.iterations
+            );
+        }
+
+        let _ = writeln!(report, "\nMemory:");
+        for (address, touches) in self.hot_memory(count) {
+            let _ = writeln!(
+                report,
+                "  {:>6}: {:>12} reads {:>12} writes",
+                address, touches.reads, touches.writes
+            );
+        }
+        report
+    }
+
+    /// The same tables as `report`, as a single JSON object.
+    pub fn to_json(&self, count: usize) -> String {
+        let opcodes = self
+            .opcodes
+            .iter()
+            .map(|(mnemonic, executions)| format!("{}:{}", json_string(mnemonic), executions))
+            .collect::<Vec<String>>();
+        let hot_spots = self
+            .hot_spots(count)
+            .iter()
+            .map(|(address, mnemonic, executions)| {
+                format!(
+                    "{{\"address\":{},\"mnemonic\":{},\"executions\":{}}}",
+                    address,
+                    json_string(mnemonic),
+                    executions
+                )
+            })
+            .collect::<Vec<String>>();
+        let loops = self
+            .loops()
+            .iter()
+            .take(count)
+            .map(|l| {
+                format!(
+                    "{{\"head\":{},\"tail\":{},\"iterations\":{}}}",
+                    l.head, l.tail, l.iterations
+                )
+            })
+            .collect::<Vec<String>>();
+        let memory = self
+            .hot_memory(count)
+            .iter()
+            .map(|(address, touches)| {
+                format!(
+                    "{{\"address\":{},\"reads\":{},\"writes\":{}}}",
+                    address, touches.reads, touches.writes
+                )
+            })
+            .collect::<Vec<String>>();
+        format!(
+            "{{\"instructions\":{},\"input_stalls\":{},\"input_wait_secs\":{},\"opcodes\":{{{}}},\"hot_spots\":[{}],\"loops\":[{}],\"memory\":[{}]}}",
+            self.instructions,
+            self.input_stalls,
+            self.input_wait.as_secs_f64(),
+            opcodes.join(","),
+            hot_spots.join(","),
+            loops.join(","),
+            memory.join(",")
+        )
+    }
+}
+
+/// `text` as a JSON string, quoted and escaped. Custom mnemonics can hold anything.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
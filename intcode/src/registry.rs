@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Command, CustomCommand, IntcodeError, Opcode, OPER_NUM_PARAMS};
+
+/// How a custom opcode uses one of its parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Param {
+    /// A value, resolved through the parameter's mode.
+    Read,
+    /// An address the handler may write to with `Call::write`.
+    Write,
+}
+
+/// What the program does after a custom opcode's handler returns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action<C = i128> {
+    /// Carry on with the next instruction.
+    Continue,
+    /// Send a value to the output, then carry on with the next instruction.
+    Output(C),
+    /// Carry on at the given address.
+    Jump(usize),
+    /// Stop, like opcode 99.
+    Halt,
+    /// Stop, and keep `code` for `Program::exit_code`.
+    Exit(C),
+}
+
+/// One execution of a custom opcode, as seen by its handler.
+pub struct Call<'a, C> {
+    ip: usize,
+    args: &'a [C],
+    params: &'a [Param],
+    writes: Writes<C>,
+}
+impl<'a, C> Call<'a, C> {
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    /// The value of a read parameter, or the target address of a write parameter.
+    pub fn arg(&self, offset: usize) -> &C {
+        &self.args[offset]
+    }
+
+    pub fn args(&self) -> &[C] {
+        self.args
+    }
+
+    /// Stores `value` at the address of the write parameter at `offset` once the
+    /// handler returns. Panics if that parameter wasn't registered as `Param::Write`.
+    pub fn write(&mut self, offset: usize, value: C) {
+        assert_eq!(
+            self.params.get(offset),
+            Some(&Param::Write),
+            "parameter {} is not a write parameter",
+            offset
+        );
+        self.writes.push((offset, value));
+    }
+}
+
+/// Values a handler stores, as (parameter offset, value).
+type Writes<C> = Vec<(usize, C)>;
+
+type Handler<C> = dyn Fn(&mut Call<'_, C>) -> Result<Action<C>, String> + Send + Sync;
+
+#[derive(Clone)]
+struct Entry<C> {
+    command: CustomCommand,
+    mnemonic: &'static str,
+    params: Vec<Param>,
+    handler: Arc<Handler<C>>,
+}
+
+/// Extra opcodes for a program to understand on top of the standard ones, installed
+/// with `Program::with_opcodes`.
+#[derive(Clone)]
+pub struct OpcodeRegistry<C = i128> {
+    entries: BTreeMap<i128, Entry<C>>,
+}
+impl<C> Default for OpcodeRegistry<C> {
+    fn default() -> Self {
+        OpcodeRegistry {
+            entries: BTreeMap::new(),
+        }
+    }
+}
+impl<C> OpcodeRegistry<C> {
+    pub fn new() -> Self {
+        OpcodeRegistry::default()
+    }
+
+    /// Adds opcode `code`, which must be two digits and not already taken by a standard
+    /// or registered opcode. Mode digits work as for the standard opcodes, so it can
+    /// take at most three parameters.
+    pub fn register<F>(
+        &mut self,
+        code: i128,
+        mnemonic: &'static str,
+        params: &[Param],
+        handler: F,
+    ) -> Result<(), IntcodeError>
+    where
+        F: Fn(&mut Call<'_, C>) -> Result<Action<C>, String> + Send + Sync + 'static,
+    {
+        let error = |message: &str| {
+            Err(IntcodeError::Register {
+                opcode: code,
+                message: String::from(message),
+            })
+        };
+        if !(0..100).contains(&code) {
+            return error("opcodes must be between 0 and 99");
+        }
+        if Opcode::new(code, 0).is_ok() {
+            return error("already a standard opcode");
+        }
+        if self.entries.contains_key(&code) {
+            return error("already registered");
+        }
+        if params.len() > OPER_NUM_PARAMS {
+            return error("too many parameters");
+        }
+        let writes = params
+            .iter()
+            .enumerate()
+            .filter(|&(_, &param)| param == Param::Write)
+            .fold(0, |mask, (offset, _)| mask | 1 << offset);
+        let command = CustomCommand {
+            code: code as u8,
+            num_params: params.len() as u8,
+            writes,
+        };
+        self.entries.insert(
+            code,
+            Entry {
+                command,
+                mnemonic,
+                params: params.to_vec(),
+                handler: Arc::new(handler),
+            },
+        );
+        Ok(())
+    }
+
+    pub(crate) fn command(&self, code: i128) -> Option<Command> {
+        self.entries
+            .get(&code)
+            .map(|entry| Command::CUSTOM(entry.command))
+    }
+
+    pub(crate) fn mnemonic(&self, code: u8) -> &'static str {
+        self.entries[&(code as i128)].mnemonic
+    }
+
+    /// Runs the handler for `code` on already resolved arguments.
+    pub(crate) fn call(
+        &self,
+        code: i128,
+        ip: usize,
+        args: &[C],
+    ) -> Result<(Action<C>, Writes<C>), String> {
+        let entry = &self.entries[&code];
+        let mut call = Call {
+            ip,
+            args,
+            params: &entry.params,
+            writes: vec![],
+        };
+        let action = (entry.handler)(&mut call)?;
+        Ok((action, call.writes))
+    }
+}
+impl<C> fmt::Debug for OpcodeRegistry<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.entries
+                    .iter()
+                    .map(|(code, entry)| (code, entry.mnemonic)),
+            )
+            .finish()
+    }
+}
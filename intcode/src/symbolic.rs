@@ -0,0 +1,609 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use crate::{Command, IntcodeError, Limit, Limits, Memory, Opcode, Program, IMMEDIATE, RELATIVE};
+
+/// Instructions a symbolic run, and each concrete run checking it, may execute.
+const MAX_STEPS: u64 = 1_000_000;
+
+/// A value built up from symbols by the instructions a program ran.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Const(i128),
+    Symbol(String),
+    Add(Rc<Expr>, Rc<Expr>),
+    Mul(Rc<Expr>, Rc<Expr>),
+    Less(Rc<Expr>, Rc<Expr>),
+    Equals(Rc<Expr>, Rc<Expr>),
+    /// Whatever memory held at an address computed from symbols.
+    Load(Rc<Expr>),
+}
+impl Expr {
+    pub fn constant(&self) -> Option<i128> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The expression as a sum of symbols times constants, if it is one.
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(value) => Some(Linear {
+                constant: *value,
+                terms: BTreeMap::new(),
+            }),
+            Expr::Symbol(name) => Some(Linear {
+                constant: 0,
+                terms: vec![(name.clone(), 1)].into_iter().collect(),
+            }),
+            Expr::Add(a, b) => a.linear()?.plus(&b.linear()?),
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                if a.terms.is_empty() {
+                    b.times(a.constant)
+                } else if b.terms.is_empty() {
+                    a.times(b.constant)
+                } else {
+                    None
+                }
+            }
+            Expr::Less(..) | Expr::Equals(..) | Expr::Load(_) => None,
+        }
+    }
+
+    /// The value with each symbol replaced by its entry in `values`. `None` if a symbol
+    /// is missing, the arithmetic overflows or the value depends on a `Load`.
+    pub fn eval(&self, values: &BTreeMap<String, i128>) -> Option<i128> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            Expr::Symbol(name) => values.get(name).copied(),
+            Expr::Add(a, b) => a.eval(values)?.checked_add(b.eval(values)?),
+            Expr::Mul(a, b) => a.eval(values)?.checked_mul(b.eval(values)?),
+            Expr::Less(a, b) => Some((a.eval(values)? < b.eval(values)?) as i128),
+            Expr::Equals(a, b) => Some((a.eval(values)? == b.eval(values)?) as i128),
+            Expr::Load(_) => None,
+        }
+    }
+
+    fn loads(&self) -> bool {
+        match self {
+            Expr::Const(_) | Expr::Symbol(_) => false,
+            Expr::Add(a, b) | Expr::Mul(a, b) | Expr::Less(a, b) | Expr::Equals(a, b) => {
+                a.loads() || b.loads()
+            }
+            Expr::Load(_) => true,
+        }
+    }
+
+    /// Folds constants, or `None` if they overflow.
+    fn add(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.checked_add(b)?),
+            (Expr::Const(0), x) | (x, Expr::Const(0)) => x,
+            (a, b) => Expr::Add(Rc::new(a), Rc::new(b)),
+        })
+    }
+
+    fn mul(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.checked_mul(b)?),
+            (Expr::Const(0), _) | (_, Expr::Const(0)) => Expr::Const(0),
+            (Expr::Const(1), x) | (x, Expr::Const(1)) => x,
+            (a, b) => Expr::Mul(Rc::new(a), Rc::new(b)),
+        })
+    }
+
+    fn compare(command: Command, a: Expr, b: Expr) -> Expr {
+        match (command, a, b) {
+            (Command::LESS, Expr::Const(a), Expr::Const(b)) => Expr::Const((a < b) as i128),
+            (_, Expr::Const(a), Expr::Const(b)) => Expr::Const((a == b) as i128),
+            (Command::LESS, a, b) => Expr::Less(Rc::new(a), Rc::new(b)),
+            (_, a, b) => Expr::Equals(Rc::new(a), Rc::new(b)),
+        }
+    }
+
+    /// How tightly the expression binds when printed.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Less(..) | Expr::Equals(..) => 0,
+            Expr::Add(..) => 1,
+            Expr::Mul(..) => 2,
+            Expr::Const(_) | Expr::Symbol(_) | Expr::Load(_) => 3,
+        }
+    }
+
+    fn operand(&self, f: &mut fmt::Formatter<'_>, parent: u8) -> fmt::Result {
+        if self.precedence() < parent {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (a, b, operator) = match self {
+            Expr::Const(value) => return write!(f, "{}", value),
+            Expr::Symbol(name) => return write!(f, "{}", name),
+            Expr::Load(address) => return write!(f, "mem[{}]", address),
+            Expr::Add(a, b) => (a, b, "+"),
+            Expr::Mul(a, b) => (a, b, "*"),
+            Expr::Less(a, b) => (a, b, "<"),
+            Expr::Equals(a, b) => (a, b, "=="),
+        };
+        let precedence = self.precedence();
+        // Comparisons don't chain, so a comparison inside one gets parentheses.
+        let inner = if precedence == 0 { 1 } else { precedence };
+        a.operand(f, inner)?;
+        write!(f, " {} ", operator)?;
+        b.operand(f, inner)
+    }
+}
+
+/// `constant` plus the sum of each symbol times its coefficient in `terms`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Linear {
+    pub constant: i128,
+    pub terms: BTreeMap<String, i128>,
+}
+impl Linear {
+    fn plus(mut self, other: &Linear) -> Option<Linear> {
+        self.constant = self.constant.checked_add(other.constant)?;
+        for (name, coefficient) in other.terms.iter() {
+            let term = self.terms.entry(name.clone()).or_insert(0);
+            *term = term.checked_add(*coefficient)?;
+            if *term == 0 {
+                self.terms.remove(name);
+            }
+        }
+        Some(self)
+    }
+
+    fn times(mut self, factor: i128) -> Option<Linear> {
+        if factor == 0 {
+            return Some(Linear::default());
+        }
+        self.constant = self.constant.checked_mul(factor)?;
+        for coefficient in self.terms.values_mut() {
+            *coefficient = coefficient.checked_mul(factor)?;
+        }
+        Some(self)
+    }
+
+    /// What the term for `unknown` must come to for the whole to equal `target`, given
+    /// the other symbols' `values`.
+    fn remainder(
+        &self,
+        target: i128,
+        unknown: Option<&str>,
+        values: &BTreeMap<String, i128>,
+    ) -> Option<i128> {
+        let mut remainder = target.checked_sub(self.constant)?;
+        for (name, coefficient) in self.terms.iter() {
+            if Some(name.as_str()) != unknown {
+                remainder = remainder.checked_sub(coefficient.checked_mul(values[name])?)?;
+            }
+        }
+        Some(remainder)
+    }
+}
+
+/// Why a program couldn't be run symbolically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stuck {
+    /// The opcode at `ip` was computed from symbols.
+    Opcode { ip: usize },
+    /// Whether or where the jump at `ip` goes depends on symbols.
+    Jump { ip: usize },
+    /// The address the instruction at `ip` writes to depends on symbols.
+    Write { ip: usize },
+    /// The instruction at `ip` moves the relative base by an amount that depends on symbols.
+    RelativeBase { ip: usize },
+    /// The program fails whatever the symbols are.
+    Error(IntcodeError),
+}
+impl fmt::Display for Stuck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stuck::Opcode { ip } => write!(f, "Symbolic opcode at {}", ip),
+            Stuck::Jump { ip } => write!(f, "Jump at {} depends on symbols", ip),
+            Stuck::Write { ip } => write!(f, "Write at {} to a symbolic address", ip),
+            Stuck::RelativeBase { ip } => {
+                write!(f, "Relative base moved by a symbolic amount at {}", ip)
+            }
+            Stuck::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// What a program left behind after running to a halt symbolically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// Every cell the program was loaded with or wrote to; the rest hold 0.
+    pub memory: BTreeMap<usize, Expr>,
+    pub outputs: Vec<Expr>,
+}
+impl Outcome {
+    /// `None` for an output the program didn't produce.
+    pub fn value(&self, goal: Goal) -> Option<Expr> {
+        match goal {
+            Goal::Memory(address) => {
+                Some(self.memory.get(&address).cloned().unwrap_or(Expr::Const(0)))
+            }
+            Goal::Output(index) => self.outputs.get(index).cloned(),
+        }
+    }
+}
+
+/// The value `SymbolicProgram::solve` looks for symbols to produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    /// The cell at this address once the program halts.
+    Memory(usize),
+    /// The output with this index, counting from 0.
+    Output(usize),
+}
+
+/// How a `Solution` was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Solved for one symbol from a linear expression for the goal.
+    Linear,
+    /// Evaluated the expression for the goal over the symbols' domains.
+    Evaluated,
+    /// Ran the program concretely over the symbols' domains, because control flow
+    /// or memory accesses depend on them.
+    Enumerated,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub values: BTreeMap<String, i128>,
+    pub method: Method,
+}
+
+#[derive(Debug, Clone)]
+struct Symbol {
+    name: String,
+    domain: RangeInclusive<i128>,
+}
+
+/// A program some of whose memory cells or inputs are unknowns, each ranging over a
+/// domain. Running it builds an expression for every cell and output as long as control
+/// flow doesn't depend on the unknowns.
+#[derive(Debug, Clone)]
+pub struct SymbolicProgram {
+    words: Vec<i128>,
+    symbols: Vec<Symbol>,
+    /// Addresses the program is loaded with a symbol at.
+    cells: Vec<(usize, String)>,
+    inputs: Vec<Expr>,
+    max_steps: u64,
+}
+impl SymbolicProgram {
+    pub fn new(words: Vec<i128>) -> Self {
+        SymbolicProgram {
+            words,
+            symbols: vec![],
+            cells: vec![],
+            inputs: vec![],
+            max_steps: MAX_STEPS,
+        }
+    }
+
+    /// Makes the cell at `address` the symbol `name`.
+    pub fn symbol(mut self, address: usize, name: &str, domain: RangeInclusive<i128>) -> Self {
+        self.declare(name, domain);
+        self.cells.push((address, name.to_string()));
+        self
+    }
+
+    /// Queues the symbol `name` as the next input.
+    pub fn symbolic_input(mut self, name: &str, domain: RangeInclusive<i128>) -> Self {
+        self.declare(name, domain);
+        self.inputs.push(Expr::Symbol(name.to_string()));
+        self
+    }
+
+    pub fn input(mut self, value: i128) -> Self {
+        self.inputs.push(Expr::Const(value));
+        self
+    }
+
+    pub fn steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    fn declare(&mut self, name: &str, domain: RangeInclusive<i128>) {
+        if !self.symbols.iter().any(|symbol| symbol.name == name) {
+            self.symbols.push(Symbol {
+                name: name.to_string(),
+                domain,
+            });
+        }
+    }
+
+    pub fn run(&self) -> Result<Outcome, Stuck> {
+        let mut state = State {
+            memory: self
+                .words
+                .iter()
+                .map(|&word| Expr::Const(word))
+                .enumerate()
+                .collect(),
+            ip: 0,
+            relative_base: 0,
+        };
+        for (address, name) in self.cells.iter() {
+            state.set(*address, Expr::Symbol(name.clone()));
+        }
+        let mut inputs = self.inputs.iter().cloned().collect::<VecDeque<Expr>>();
+        let mut outputs = vec![];
+        for _ in 0..self.max_steps {
+            let ip = state.ip;
+            let raw = state.get(ip).constant().ok_or(Stuck::Opcode { ip })?;
+            let opcode = Opcode::new(raw, ip).map_err(Stuck::Error)?;
+            // Operands are found by adding to ip, once it's clear they all have addresses.
+            let mut next = ip
+                .checked_add(opcode.command.num_params() + 1)
+                .ok_or(Stuck::Error(IntcodeError::Overflow { ip, opcode: raw }))?;
+            match opcode.command {
+                Command::ADD | Command::MULTIPLY => {
+                    let (a, b) = (state.read(&opcode, 0)?, state.read(&opcode, 1)?);
+                    let value = if opcode.command == Command::ADD {
+                        Expr::add(a, b)
+                    } else {
+                        Expr::mul(a, b)
+                    };
+                    let value =
+                        value.ok_or(Stuck::Error(IntcodeError::Overflow { ip, opcode: raw }))?;
+                    state.write(&opcode, 2, value)?;
+                }
+                Command::LESS | Command::EQUALS => {
+                    let (a, b) = (state.read(&opcode, 0)?, state.read(&opcode, 1)?);
+                    state.write(&opcode, 2, Expr::compare(opcode.command, a, b))?;
+                }
+                Command::INPUT => {
+                    let value = inputs
+                        .pop_front()
+                        .ok_or(Stuck::Error(IntcodeError::MissingInput { ip, opcode: raw }))?;
+                    state.write(&opcode, 0, value)?;
+                }
+                Command::OUTPUT => outputs.push(state.read(&opcode, 0)?),
+                Command::JIT | Command::JIF => {
+                    let condition = state.read(&opcode, 0)?;
+                    let condition = condition.constant().ok_or(Stuck::Jump { ip })?;
+                    if (condition != 0) == (opcode.command == Command::JIT) {
+                        let target = state.read(&opcode, 1)?;
+                        let target = target.constant().ok_or(Stuck::Jump { ip })?;
+                        next = usize::try_from(target).map_err(|_| {
+                            Stuck::Error(IntcodeError::NegativeAddress {
+                                ip,
+                                opcode: raw,
+                                address: target,
+                            })
+                        })?;
+                    }
+                }
+                Command::REL => {
+                    let change = state.read(&opcode, 0)?;
+                    let change = change.constant().ok_or(Stuck::RelativeBase { ip })?;
+                    state.relative_base = state
+                        .relative_base
+                        .checked_add(change)
+                        .ok_or(Stuck::Error(IntcodeError::Overflow { ip, opcode: raw }))?;
+                }
+                Command::STOP => {
+                    return Ok(Outcome {
+                        memory: state.memory,
+                        outputs,
+                    })
+                }
+                Command::CUSTOM(_) => unreachable!("Opcode::new only decodes standard opcodes"),
+            }
+            state.ip = next;
+        }
+        Err(Stuck::Error(IntcodeError::LimitExceeded {
+            ip: state.ip,
+            limit: Limit::Steps(self.max_steps),
+        }))
+    }
+
+    /// Finds values in the symbols' domains for which `goal` comes to `target`. Works
+    /// from the expression for the goal when the program runs symbolically, and
+    /// otherwise tries every assignment with the first symbol declared varying slowest.
+    pub fn solve(&self, goal: Goal, target: i128) -> Option<Solution> {
+        let outcome = match self.run() {
+            Ok(outcome) => outcome,
+            Err(_) => return self.enumerate(goal, target),
+        };
+        // Every assignment takes the same path, so none produces a missing output.
+        let expr = outcome.value(goal)?;
+        if let Some(linear) = expr.linear() {
+            let values = self.solve_linear(&linear, target)?;
+            // Concrete runs can still overflow where the expression didn't.
+            if self.concrete(&values, goal) == Some(target) {
+                return Some(Solution {
+                    values,
+                    method: Method::Linear,
+                });
+            }
+        } else if !expr.loads() {
+            let values = search(&self.symbols, |values| expr.eval(values) == Some(target))?;
+            if self.concrete(&values, goal) == Some(target) {
+                return Some(Solution {
+                    values,
+                    method: Method::Evaluated,
+                });
+            }
+        }
+        self.enumerate(goal, target)
+    }
+
+    /// Enumerates all symbols but the last one in `linear`, which is solved for.
+    fn solve_linear(&self, linear: &Linear, target: i128) -> Option<BTreeMap<String, i128>> {
+        let unknown = self
+            .symbols
+            .iter()
+            .rev()
+            .find(|symbol| linear.terms.contains_key(&symbol.name));
+        let unknown_name = unknown.map(|symbol| symbol.name.as_str());
+        let known = self
+            .symbols
+            .iter()
+            .filter(|symbol| Some(symbol.name.as_str()) != unknown_name)
+            .cloned()
+            .collect::<Vec<Symbol>>();
+        search(&known, |values| {
+            let remainder = match linear.remainder(target, unknown_name, values) {
+                Some(remainder) => remainder,
+                None => return false,
+            };
+            let unknown = match unknown {
+                Some(unknown) => unknown,
+                None => return remainder == 0,
+            };
+            let coefficient = linear.terms[&unknown.name];
+            match (
+                remainder.checked_rem(coefficient),
+                remainder.checked_div(coefficient),
+            ) {
+                (Some(0), Some(value)) if unknown.domain.contains(&value) => {
+                    values.insert(unknown.name.clone(), value);
+                    true
+                }
+                _ => false,
+            }
+        })
+    }
+
+    fn enumerate(&self, goal: Goal, target: i128) -> Option<Solution> {
+        let values = search(&self.symbols, |values| {
+            self.concrete(values, goal) == Some(target)
+        })?;
+        Some(Solution {
+            values,
+            method: Method::Enumerated,
+        })
+    }
+
+    /// The goal after running the program with the symbols set to `values`, or `None`
+    /// if it fails.
+    fn concrete(&self, values: &BTreeMap<String, i128>, goal: Goal) -> Option<i128> {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| input.eval(values))
+            .collect::<Option<Vec<i128>>>()?;
+        let mut program = Program::from_cells(self.words.clone(), &inputs)
+            .with_limits(Limits::new().steps(self.max_steps));
+        for (address, name) in self.cells.iter() {
+            program.set(*address, values[name]);
+        }
+        let outputs = program.run().ok()?;
+        match goal {
+            Goal::Memory(address) => Some(program.memory().get(address)),
+            Goal::Output(index) => outputs.get(index).copied(),
+        }
+    }
+}
+
+/// Tries every assignment of `symbols` in order, the first one varying slowest, until
+/// `accept` takes one. `accept` may add values for other symbols to it.
+fn search<F>(symbols: &[Symbol], mut accept: F) -> Option<BTreeMap<String, i128>>
+where
+    F: FnMut(&mut BTreeMap<String, i128>) -> bool,
+{
+    if symbols.iter().any(|symbol| symbol.domain.is_empty()) {
+        return None;
+    }
+    let mut current = symbols
+        .iter()
+        .map(|symbol| *symbol.domain.start())
+        .collect::<Vec<i128>>();
+    loop {
+        let mut values = symbols
+            .iter()
+            .map(|symbol| symbol.name.clone())
+            .zip(current.iter().copied())
+            .collect();
+        if accept(&mut values) {
+            return Some(values);
+        }
+        let mut index = symbols.len();
+        loop {
+            if index == 0 {
+                return None;
+            }
+            index -= 1;
+            if current[index] < *symbols[index].domain.end() {
+                current[index] += 1;
+                break;
+            }
+            current[index] = *symbols[index].domain.start();
+        }
+    }
+}
+
+struct State {
+    memory: BTreeMap<usize, Expr>,
+    ip: usize,
+    relative_base: i128,
+}
+impl State {
+    fn get(&self, address: usize) -> Expr {
+        self.memory.get(&address).cloned().unwrap_or(Expr::Const(0))
+    }
+
+    fn set(&mut self, address: usize, value: Expr) {
+        self.memory.insert(address, value);
+    }
+
+    /// The address parameter `offset` of the instruction at `ip` refers to.
+    fn address(&self, opcode: &Opcode, offset: usize) -> Result<Expr, Stuck> {
+        let word = self.get(self.ip + 1 + offset);
+        if opcode.modes()[offset] != RELATIVE {
+            return Ok(word);
+        }
+        Expr::add(word, Expr::Const(self.relative_base)).ok_or(Stuck::Error(
+            IntcodeError::Overflow {
+                ip: self.ip,
+                opcode: opcode.raw,
+            },
+        ))
+    }
+
+    fn concrete_address(&self, opcode: &Opcode, address: i128) -> Result<usize, Stuck> {
+        usize::try_from(address).map_err(|_| {
+            Stuck::Error(IntcodeError::NegativeAddress {
+                ip: self.ip,
+                opcode: opcode.raw,
+                address,
+            })
+        })
+    }
+
+    fn read(&self, opcode: &Opcode, offset: usize) -> Result<Expr, Stuck> {
+        if opcode.modes()[offset] == IMMEDIATE {
+            return Ok(self.get(self.ip + 1 + offset));
+        }
+        match self.address(opcode, offset)? {
+            Expr::Const(address) => Ok(self.get(self.concrete_address(opcode, address)?)),
+            address => Ok(Expr::Load(Rc::new(address))),
+        }
+    }
+
+    fn write(&mut self, opcode: &Opcode, offset: usize, value: Expr) -> Result<(), Stuck> {
+        let address = match self.address(opcode, offset)? {
+            Expr::Const(address) => self.concrete_address(opcode, address)?,
+            _ => return Err(Stuck::Write { ip: self.ip }),
+        };
+        self.set(address, value);
+        Ok(())
+    }
+}
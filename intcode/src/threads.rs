@@ -0,0 +1,290 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::network::IDLE_POLLS;
+use crate::{Cell, Event, InputSource, IntcodeError, Memory, Message, Program, Router, StepResult};
+
+/// State the machine threads and the controlling `ThreadedNetwork` both look at.
+struct Shared<C> {
+    stop: AtomicBool,
+    /// Values sent to a machine that it hasn't read yet.
+    in_flight: AtomicUsize,
+    /// Timeouts in a row each machine has waited through, reset when it reads or
+    /// outputs a value.
+    polls: Vec<AtomicUsize>,
+    finished: Vec<AtomicBool>,
+    last_outputs: Mutex<Vec<Option<C>>>,
+}
+
+enum Notice<C> {
+    Message(Message<C>),
+    Finished(Result<(), IntcodeError>),
+}
+
+/// A machine's input inside its thread: blocks on the channel, counting a poll each
+/// time `timeout` passes without a value. Messages arrive whole, so values from two
+/// senders never interleave.
+struct ThreadInput<C> {
+    index: usize,
+    receiver: Receiver<Vec<C>>,
+    pending: VecDeque<C>,
+    timeout: Duration,
+    idle_input: Option<C>,
+    shared: Arc<Shared<C>>,
+}
+impl<C: Clone> InputSource<C> for ThreadInput<C> {
+    fn next_input(&mut self) -> Option<C> {
+        loop {
+            if self.shared.stop.load(Ordering::SeqCst) {
+                return None;
+            }
+            if let Some(value) = self.pending.pop_front() {
+                self.shared.polls[self.index].store(0, Ordering::SeqCst);
+                self.shared.in_flight.fetch_sub(1, Ordering::SeqCst);
+                return Some(value);
+            }
+            match self.receiver.recv_timeout(self.timeout) {
+                Ok(values) => self.pending.extend(values),
+                Err(RecvTimeoutError::Timeout) => {
+                    self.shared.polls[self.index].fetch_add(1, Ordering::SeqCst);
+                    if let Some(value) = &self.idle_input {
+                        return Some(value.clone());
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+}
+
+/// Runs every program of a network on its own thread, connected by channels. Routing
+/// and events work as for `Network`, but machines run concurrently, so the order
+/// messages interleave in isn't fixed and idle is detected by timeouts.
+///
+/// Dropping the network stops and joins every thread.
+pub struct ThreadedNetwork<C: Cell = i128> {
+    senders: Vec<Sender<Vec<C>>>,
+    notices: Receiver<Notice<C>>,
+    handles: Vec<JoinHandle<()>>,
+    shared: Arc<Shared<C>>,
+    timeout: Duration,
+    running: usize,
+    /// `shared.polls` as of the last idle check.
+    polls_seen: Vec<usize>,
+}
+impl<C: Cell> ThreadedNetwork<C> {
+    /// Starts one thread per machine. Reads block until a value arrives; a machine
+    /// that has waited two timeouts in a row and is still waiting counts as idle.
+    pub fn spawn<R, M>(
+        machines: Vec<Program<VecDeque<C>, (), M>>,
+        router: R,
+        timeout: Duration,
+    ) -> Self
+    where
+        R: Router<C> + Clone + Send + 'static,
+        M: Memory<Cell = C> + Send + 'static,
+    {
+        ThreadedNetwork::start(machines, router, timeout, None)
+    }
+
+    /// Like `spawn`, but a read that waits `timeout` gets `idle_input` instead of
+    /// blocking on, like the -1 of a non-blocking network card.
+    pub fn spawn_polling<R, M>(
+        machines: Vec<Program<VecDeque<C>, (), M>>,
+        router: R,
+        timeout: Duration,
+        idle_input: C,
+    ) -> Self
+    where
+        R: Router<C> + Clone + Send + 'static,
+        M: Memory<Cell = C> + Send + 'static,
+    {
+        ThreadedNetwork::start(machines, router, timeout, Some(idle_input))
+    }
+
+    fn start<R, M>(
+        machines: Vec<Program<VecDeque<C>, (), M>>,
+        router: R,
+        timeout: Duration,
+        idle_input: Option<C>,
+    ) -> Self
+    where
+        R: Router<C> + Clone + Send + 'static,
+        M: Memory<Cell = C> + Send + 'static,
+    {
+        let count = machines.len();
+        let shared = Arc::new(Shared {
+            stop: AtomicBool::new(false),
+            in_flight: AtomicUsize::new(0),
+            polls: (0..count).map(|_| AtomicUsize::new(0)).collect(),
+            finished: (0..count).map(|_| AtomicBool::new(false)).collect(),
+            last_outputs: Mutex::new(vec![None; count]),
+        });
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..count).map(|_| mpsc::channel()).unzip();
+        let (notify, notices) = mpsc::channel();
+
+        let mut handles = vec![];
+        for (index, (mut program, receiver)) in machines.into_iter().zip(receivers).enumerate() {
+            let queued: VecDeque<C> = program.input_mut().drain(..).collect();
+            shared.in_flight.fetch_add(queued.len(), Ordering::SeqCst);
+            let program = program.with_input(ThreadInput {
+                index,
+                receiver,
+                pending: queued,
+                timeout,
+                idle_input: idle_input.clone(),
+                shared: shared.clone(),
+            });
+            let machine = Machine {
+                index,
+                senders: senders.clone(),
+                router: router.clone(),
+                notify: notify.clone(),
+                shared: shared.clone(),
+            };
+            handles.push(thread::spawn(move || machine.run(program)));
+        }
+        ThreadedNetwork {
+            senders,
+            notices,
+            handles,
+            shared,
+            timeout,
+            running: count,
+            polls_seen: vec![0; count],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+
+    /// The most recent value machine `index` output, wherever it was routed.
+    pub fn last_output(&self, index: usize) -> Option<C> {
+        self.shared.last_outputs.lock().unwrap()[index].clone()
+    }
+
+    /// Queues `values` on machine `to`'s input.
+    pub fn send(&mut self, to: usize, values: &[C]) {
+        send(&self.shared, &self.senders[to], values);
+    }
+
+    /// Waits until a message leaves the network, the network stays idle for a whole
+    /// timeout, or every machine has stopped. A machine's error is returned as soon as
+    /// it happens; the other machines keep running.
+    pub fn next_event(&mut self) -> Result<Event<C>, IntcodeError> {
+        loop {
+            if self.running == 0 {
+                return Ok(Event::Halted);
+            }
+            match self.notices.recv_timeout(self.timeout) {
+                Ok(Notice::Message(message)) => return Ok(Event::Message(message)),
+                Ok(Notice::Finished(result)) => {
+                    self.running -= 1;
+                    result?;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.is_idle() {
+                        return Ok(Event::Idle);
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(Event::Halted),
+            }
+        }
+    }
+
+    /// A machine is idle once it has polled nothing `IDLE_POLLS` times in a row and
+    /// polled again since the last check, so one that went back to computing after an
+    /// empty poll doesn't count.
+    fn is_idle(&mut self) -> bool {
+        let shared = &self.shared;
+        let polls: Vec<usize> = shared
+            .polls
+            .iter()
+            .map(|polls| polls.load(Ordering::SeqCst))
+            .collect();
+        let idle = shared.in_flight.load(Ordering::SeqCst) == 0
+            && (0..polls.len()).all(|index| {
+                shared.finished[index].load(Ordering::SeqCst)
+                    || (polls[index] >= IDLE_POLLS && polls[index] > self.polls_seen[index])
+            });
+        self.polls_seen = polls;
+        idle
+    }
+
+    /// Stops every machine and waits for the threads to exit.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.shared.stop.store(true, Ordering::SeqCst);
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+impl<C: Cell> Drop for ThreadedNetwork<C> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn send<C: Clone>(shared: &Shared<C>, sender: &Sender<Vec<C>>, values: &[C]) {
+    shared.in_flight.fetch_add(values.len(), Ordering::SeqCst);
+    if sender.send(values.to_vec()).is_err() {
+        // The machine already stopped and will never read it.
+        shared.in_flight.fetch_sub(values.len(), Ordering::SeqCst);
+    }
+}
+
+/// Everything a machine's thread needs besides its program.
+struct Machine<C, R> {
+    index: usize,
+    senders: Vec<Sender<Vec<C>>>,
+    router: R,
+    notify: Sender<Notice<C>>,
+    shared: Arc<Shared<C>>,
+}
+impl<C: Cell, R: Router<C>> Machine<C, R> {
+    fn run<M: Memory<Cell = C>>(mut self, mut program: Program<ThreadInput<C>, (), M>) {
+        let result = self.execute(&mut program);
+        self.shared.finished[self.index].store(true, Ordering::SeqCst);
+        let _ = self.notify.send(Notice::Finished(result));
+    }
+
+    fn execute<M: Memory<Cell = C>>(
+        &mut self,
+        program: &mut Program<ThreadInput<C>, (), M>,
+    ) -> Result<(), IntcodeError> {
+        while !self.shared.stop.load(Ordering::Relaxed) {
+            match program.execute()? {
+                StepResult::Output(value) => {
+                    self.shared.polls[self.index].store(0, Ordering::SeqCst);
+                    self.shared.last_outputs.lock().unwrap()[self.index] = Some(value.clone());
+                    let message = self.router.route(self.index, value, self.senders.len());
+                    match message {
+                        Some(message) if message.to < self.senders.len() => {
+                            send(&self.shared, &self.senders[message.to], &message.values)
+                        }
+                        Some(message) => {
+                            let _ = self.notify.send(Notice::Message(message));
+                        }
+                        None => {}
+                    }
+                }
+                StepResult::Continued => {}
+                StepResult::NeedsInput | StepResult::Halted => break,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::{Cell, IntcodeError, Program, StepResult};
+
+// One entry per line, fields separated by spaces:
+//
+//     <ip> <opcode> [<param>,<param>,...] [w<address>=<value>] [i<input>] [o<output>]
+//
+// e.g. `4 1002 7,3,12 w12=21` or `9 4 21 o21`. Params are the values the instruction
+// saw after resolving modes; a write parameter is listed as its target address.
+
+/// What one executed instruction did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry<C = i128> {
+    pub ip: usize,
+    pub opcode: i128,
+    pub params: Vec<C>,
+    pub write: Option<(usize, C)>,
+    pub input: Option<C>,
+    pub output: Option<C>,
+}
+impl<C: fmt::Display> fmt::Display for TraceEntry<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.ip, self.opcode)?;
+        if !self.params.is_empty() {
+            let params = self
+                .params
+                .iter()
+                .map(|param| param.to_string())
+                .collect::<Vec<String>>();
+            write!(f, " {}", params.join(","))?;
+        }
+        if let Some((address, value)) = &self.write {
+            write!(f, " w{}={}", address, value)?;
+        }
+        if let Some(input) = &self.input {
+            write!(f, " i{}", input)?;
+        }
+        if let Some(output) = &self.output {
+            write!(f, " o{}", output)?;
+        }
+        Ok(())
+    }
+}
+impl<C: Cell> TraceEntry<C> {
+    fn parse(line: usize, text: &str) -> Result<TraceEntry<C>, IntcodeError> {
+        let error = |message: String| IntcodeError::Trace { line, message };
+        let number = |field: &str| {
+            field
+                .parse::<C>()
+                .map_err(|_| error(format!("Invalid number {:?}", field)))
+        };
+        let mut fields = text.split_whitespace();
+        let ip = match fields.next().map(|field| field.parse::<usize>()) {
+            Some(Ok(ip)) => ip,
+            _ => return Err(error(format!("Missing ip in {:?}", text))),
+        };
+        let opcode = match fields.next() {
+            Some(field) => field
+                .parse::<i128>()
+                .map_err(|_| error(format!("Invalid opcode {:?}", field)))?,
+            None => return Err(error(format!("Missing opcode in {:?}", text))),
+        };
+        let mut entry = TraceEntry {
+            ip,
+            opcode,
+            params: vec![],
+            write: None,
+            input: None,
+            output: None,
+        };
+        for field in fields {
+            if let Some(write) = field.strip_prefix('w') {
+                let (address, value) = match write.find('=') {
+                    Some(i) => (&write[..i], &write[i + 1..]),
+                    None => return Err(error(format!("Invalid write {:?}", field))),
+                };
+                let address = address
+                    .parse::<usize>()
+                    .map_err(|_| error(format!("Invalid write {:?}", field)))?;
+                entry.write = Some((address, number(value)?));
+            } else if let Some(input) = field.strip_prefix('i') {
+                entry.input = Some(number(input)?);
+            } else if let Some(output) = field.strip_prefix('o') {
+                entry.output = Some(number(output)?);
+            } else {
+                entry.params = field
+                    .split(',')
+                    .map(number)
+                    .collect::<Result<Vec<C>, IntcodeError>>()?;
+            }
+        }
+        Ok(entry)
+    }
+}
+
+/// Receives an entry for every instruction a traced program executes.
+pub trait TraceSink<C = i128> {
+    fn record(&mut self, entry: &TraceEntry<C>);
+}
+
+/// A trace held in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace<C = i128> {
+    entries: Vec<TraceEntry<C>>,
+}
+impl<C> Default for Trace<C> {
+    fn default() -> Self {
+        Trace { entries: vec![] }
+    }
+}
+impl Trace {
+    /// Reads the format `Display` writes. Blank lines are skipped.
+    pub fn parse(text: &str) -> Result<Trace, IntcodeError> {
+        Trace::parse_cells(text)
+    }
+}
+impl<C: Cell> Trace<C> {
+    pub fn new() -> Self {
+        Trace::default()
+    }
+
+    /// Like `parse`, for traces of programs on other cell types.
+    pub fn parse_cells(text: &str) -> Result<Trace<C>, IntcodeError> {
+        let mut entries = vec![];
+        for (i, line) in text.lines().enumerate() {
+            if !line.trim().is_empty() {
+                entries.push(TraceEntry::parse(i + 1, line)?);
+            }
+        }
+        Ok(Trace { entries })
+    }
+
+    pub fn entries(&self) -> &[TraceEntry<C>] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Every input the program consumed, in order.
+    pub fn inputs(&self) -> Vec<C> {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.input.clone())
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Vec<C> {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.output.clone())
+            .collect()
+    }
+
+    /// The first step at which the traces differ, including one ending before the other.
+    pub fn first_divergence(&self, other: &Trace<C>) -> Option<Divergence<C>> {
+        let index = match self
+            .entries
+            .iter()
+            .zip(other.entries.iter())
+            .position(|(left, right)| left != right)
+        {
+            Some(index) => index,
+            None if self.len() == other.len() => return None,
+            None => self.len().min(other.len()),
+        };
+        Some(Divergence {
+            index,
+            left: self.entries.get(index).cloned(),
+            right: other.entries.get(index).cloned(),
+        })
+    }
+}
+impl<C: Clone> TraceSink<C> for Trace<C> {
+    fn record(&mut self, entry: &TraceEntry<C>) {
+        self.entries.push(entry.clone());
+    }
+}
+impl<C: fmt::Display> fmt::Display for Trace<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Streams entries out as lines as they happen, so a long run never holds its whole
+/// trace in memory. The first write error is kept and returned from `flush`.
+#[derive(Debug)]
+pub struct TraceWriter<W: Write> {
+    writer: W,
+    error: Option<io::Error>,
+}
+impl<W: Write> TraceWriter<W> {
+    pub fn new(writer: W) -> Self {
+        TraceWriter {
+            writer,
+            error: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush(),
+        }
+    }
+}
+impl<C: fmt::Display, W: Write> TraceSink<C> for TraceWriter<W> {
+    fn record(&mut self, entry: &TraceEntry<C>) {
+        if self.error.is_none() {
+            if let Err(error) = writeln!(self.writer, "{}", entry) {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+/// Where two traces first disagree; `None` on one side means that trace had already ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence<C = i128> {
+    pub index: usize,
+    pub left: Option<TraceEntry<C>>,
+    pub right: Option<TraceEntry<C>>,
+}
+impl<C: fmt::Display> fmt::Display for Divergence<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |entry: &Option<TraceEntry<C>>| match entry {
+            Some(entry) => entry.to_string(),
+            None => String::from("end of trace"),
+        };
+        write!(
+            f,
+            "Traces diverge at step {}:\n< {}\n> {}",
+            self.index,
+            show(&self.left),
+            show(&self.right)
+        )
+    }
+}
+
+/// The sink a traced program records into. Clones of the program share it.
+pub(crate) struct Tracer<C>(pub(crate) Arc<Mutex<dyn TraceSink<C> + Send>>);
+impl<C> Tracer<C> {
+    pub(crate) fn record(&self, entry: &TraceEntry<C>) {
+        self.0.lock().unwrap().record(entry);
+    }
+}
+impl<C> Clone for Tracer<C> {
+    fn clone(&self) -> Self {
+        Tracer(self.0.clone())
+    }
+}
+impl<C> fmt::Debug for Tracer<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tracer")
+    }
+}
+
+/// Re-runs the program in `cells` on the inputs recorded in `trace` and compares the new
+/// trace against it. Stops once the new run is one step longer than the recording, so a
+/// program that diverges into a loop still returns.
+pub fn replay(cells: Vec<i128>, trace: &Trace) -> Result<Option<Divergence>, IntcodeError> {
+    let recorded = Arc::new(Mutex::new(Trace::new()));
+    let mut program = Program::from_cells(cells, &trace.inputs()).with_trace(recorded.clone());
+    let mut steps = 0;
+    while steps <= trace.len() {
+        match program.execute()? {
+            StepResult::NeedsInput | StepResult::Halted => break,
+            _ => steps += 1,
+        }
+    }
+    let recorded = recorded.lock().unwrap();
+    Ok(trace.first_divergence(&recorded))
+}
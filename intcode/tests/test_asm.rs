@@ -0,0 +1,94 @@
+use intcode::{assemble, assemble_words, disassemble, process_program, IntcodeError};
+
+#[cfg(test)]
+mod tests_asm {
+    use super::*;
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            ; Outputs 1 if the input is 8 and 0 otherwise.
+                    IN [x]
+                    EQ [x], #8, [x]
+                    OUT [x]
+                    HALT
+            x:      db -1
+        ";
+        assert_eq!(assemble(source).unwrap(), "3,9,1008,9,8,9,4,9,99,-1");
+        let (_p, result) = process_program(&assemble(source).unwrap(), &[8]).unwrap();
+        assert_eq!(result, [1]);
+    }
+
+    #[test]
+    fn test_labels_and_modes() {
+        let source = "
+            start:  arb #stack
+                    add [rb+1], #-2, [r-1]
+                    jt #1, #end
+                    db \"hi\\n\", 7
+            end:    mul [data+1], [rb], [data]
+                    halt
+            data:   db end, data
+            stack:
+        ";
+        assert_eq!(
+            assemble_words(source).unwrap(),
+            [
+                109, 20, 21201, 1, -2, -1, 1105, 1, 13, 104, 105, 10, 7, 2002, 19, 0, 18, 99, 13,
+                18
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let programs = [
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+            "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9",
+            "3,3,1105,-1,9,1101,0,0,12,4,12,99,1",
+            "1,0,0,3,1,1,2,3,1,3,4,3,1,5,0,3,2,1,10,19,1,6,19,23,99,42,21101",
+        ];
+        for program in programs.iter() {
+            let source = disassemble(program).unwrap().source();
+            assert_eq!(assemble(&source).unwrap(), *program, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |line, message: &str| {
+            Err(IntcodeError::Assembly {
+                line,
+                message: String::from(message),
+            })
+        };
+        assert_eq!(assemble("\n  FOO #1"), error(2, "Unknown mnemonic \"FOO\""));
+        assert_eq!(
+            assemble("ADD #1, #2"),
+            error(1, "ADD takes 3 operands but got 2")
+        );
+        assert_eq!(
+            assemble("JT #1, #nowhere"),
+            error(1, "Undefined label \"nowhere\"")
+        );
+        assert_eq!(
+            assemble("a: HALT\na: HALT"),
+            error(2, "Duplicate label \"a\"")
+        );
+        assert_eq!(assemble("OUT 5"), error(1, "Invalid operand \"5\""));
+        assert_eq!(
+            assemble("x: add [r--170141183460469231731687303715884105728], #0, x"),
+            error(
+                1,
+                "Invalid offset in \"[r--170141183460469231731687303715884105728]\""
+            )
+        );
+        assert_eq!(
+            assemble("HALT\nx: db x+170141183460469231731687303715884105727"),
+            error(
+                2,
+                "x+170141183460469231731687303715884105727 is out of range"
+            )
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use intcode::{sleep, Channel, Executor, InputSource, IntcodeError, Program};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests_async {
+    use super::*;
+
+    const FEEDBACK: &str =
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+    // Reads until it gets something other than -1, then halts.
+    const POLLER: &str = "3,12,1008,12,-1,13,1005,13,0,99,0,0,0,0";
+
+    #[test]
+    fn test_run_async() {
+        let mut executor = Executor::new();
+        let mut program = Program::new("3,0,4,0,99", &[8]).unwrap();
+        assert_eq!(executor.run_until(program.run_async()), Some(Ok(vec![8])));
+
+        let mut program = Program::new("3,0,4,0,99", &[]).unwrap();
+        assert_eq!(
+            executor.run_until(program.run_async()),
+            Some(Err(IntcodeError::MissingInput { ip: 0, opcode: 3 }))
+        );
+    }
+
+    #[test]
+    fn test_ring_of_channels() {
+        let channels: Vec<Channel> = (0..5).map(|_| Channel::new()).collect();
+        let mut executor = Executor::new();
+        for (index, &phase) in [9, 8, 7, 6, 5].iter().enumerate() {
+            channels[index].send(phase);
+            let mut amp = Program::new(FEEDBACK, &[])
+                .unwrap()
+                .with_input(channels[index].clone())
+                .with_output(channels[(index + 1) % 5].clone());
+            executor.spawn(async move {
+                amp.run_async().await.unwrap();
+            });
+        }
+        channels[0].send(0);
+        assert!(executor.run());
+        assert_eq!(channels[0].len(), 1);
+        assert_eq!(channels[0].clone().next_input(), Some(139629729));
+    }
+
+    #[test]
+    fn test_timers_and_polling() {
+        let channels: Vec<Channel> = (0..3).map(|_| Channel::new().with_idle_input(-1)).collect();
+        let mut executor = Executor::new();
+        for channel in &channels {
+            let mut machine = Program::new(POLLER, &[])
+                .unwrap()
+                .with_input(channel.clone());
+            executor.spawn(async move {
+                machine.run_async().await.unwrap();
+            });
+        }
+        let polls = executor.run_until(async {
+            sleep(Duration::from_millis(5)).await;
+            let polls: Vec<usize> = channels.iter().map(Channel::polls).collect();
+            for channel in &channels {
+                channel.send(7);
+            }
+            polls
+        });
+        assert!(polls.unwrap().iter().all(|&polls| polls > 1));
+        assert!(executor.run());
+    }
+
+    #[test]
+    fn test_stuck() {
+        let mut executor = Executor::new();
+        let mut program = Program::new("3,0,99", &[])
+            .unwrap()
+            .with_input(Channel::new());
+        executor.spawn(async move {
+            program.run_async().await.unwrap();
+        });
+        assert!(!executor.run());
+        assert_eq!(
+            executor.run_until(sleep(Duration::from_millis(1))),
+            Some(())
+        );
+    }
+}
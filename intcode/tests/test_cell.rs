@@ -0,0 +1,65 @@
+use intcode::{Arithmetic, IntcodeError, Memory, Program, VecMemory};
+
+#[cfg(test)]
+mod tests_cell {
+    use super::*;
+
+    // Squares its input, then squares the result again.
+    const SQUARE_TWICE: &str = "3,13,2,13,13,13,2,13,13,13,4,13,99,0";
+
+    #[test]
+    fn test_i64_cells() {
+        let mut program = Program::<_, _, VecMemory<i64>>::load(SQUARE_TWICE, &[30_000]).unwrap();
+        assert_eq!(program.run().unwrap(), [810_000_000_000_000_000i64]);
+    }
+
+    #[test]
+    fn test_overflow_reports_ip() {
+        let mut program =
+            Program::<_, _, VecMemory<i64>>::load(SQUARE_TWICE, &[3_000_000]).unwrap();
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::Overflow { ip: 6, opcode: 2 })
+        );
+
+        let mut program = Program::new(SQUARE_TWICE, &[1 << 40]).unwrap();
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::Overflow { ip: 6, opcode: 2 })
+        );
+    }
+
+    #[test]
+    fn test_wrapping_arithmetic() {
+        let mut program = Program::<_, _, VecMemory<i32>>::load("1101,2147483647,1,5,99,0", &[])
+            .unwrap()
+            .with_arithmetic(Arithmetic::Wrapping);
+        program.run().unwrap();
+        assert_eq!(program.memory().get(5), i32::MIN);
+    }
+
+    #[test]
+    fn test_oversized_address() {
+        let mut program = Program::<_, _, VecMemory<i128>>::load(
+            "4,170141183460469231731687303715884105727,99",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::Overflow { ip: 0, opcode: 4 })
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_cells() {
+        use intcode::BigInt;
+
+        let mut program =
+            Program::<_, _, VecMemory<BigInt>>::load(SQUARE_TWICE, &[BigInt::from(1u64 << 40)])
+                .unwrap();
+        let expected = BigInt::from(1u64 << 40).pow(4);
+        assert_eq!(program.run().unwrap(), [expected]);
+    }
+}
@@ -0,0 +1,129 @@
+use intcode::{
+    assemble_words, parse_text, ControlFlowGraph, Disassembly, Edge, EdgeKind, Exit, Target,
+};
+
+#[cfg(test)]
+mod tests_cfg {
+    use super::*;
+
+    // Reads x, doubles it in a subroutine and prints it.
+    const DOUBLE: &str = "
+                ARB #stack          ; 0
+                IN [x]              ; 2
+                ADD #ret, #0, [rb]  ; 4
+                JT #1, #double      ; 8
+        ret:    OUT [x]             ; 11
+                HALT                ; 13
+        double: ARB #1              ; 14
+                MUL [x], #2, [x]    ; 16
+                ARB #-1             ; 20
+                JT #1, [rb]         ; 22
+        x:      db 0                ; 25
+        stack:  db 0, 0             ; 26
+    ";
+
+    fn analyse(source: &str) -> ControlFlowGraph {
+        ControlFlowGraph::new(&Disassembly::new(assemble_words(source).unwrap()))
+    }
+
+    #[test]
+    fn test_blocks_and_edges() {
+        let graph = analyse(DOUBLE);
+        let starts = graph.blocks().map(|block| block.start).collect::<Vec<_>>();
+        assert_eq!(starts, [0, 11, 14]);
+        assert_eq!(
+            graph.block_at(0).unwrap().exit,
+            Exit::Call {
+                entry: Target::Known(14),
+                return_site: 11
+            }
+        );
+        assert_eq!(graph.block_at(11).unwrap().exit, Exit::Halt);
+        assert_eq!(graph.block_at(14).unwrap().exit, Exit::Return);
+        assert_eq!(graph.block_containing(20).unwrap().start, 14);
+        assert!(graph.block_containing(25).is_none());
+        assert_eq!(
+            graph.edges(),
+            [
+                Edge {
+                    from: 0,
+                    to: 14,
+                    kind: EdgeKind::Call
+                },
+                Edge {
+                    from: 0,
+                    to: 11,
+                    kind: EdgeKind::Resume
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_subroutines() {
+        let graph = analyse(DOUBLE);
+        let double = graph.subroutine_at(14).unwrap();
+        assert_eq!(double.callers, [8]);
+        assert_eq!(double.blocks.iter().copied().collect::<Vec<_>>(), [14]);
+        assert_eq!(double.frame, Some(1));
+        assert!(double.balanced);
+
+        // Forgetting to pop the frame on one path.
+        let unbalanced = analyse(&DOUBLE.replace("ARB #-1 ", "ARB #-2 "));
+        assert!(!unbalanced.subroutine_at(14).unwrap().balanced);
+        assert_eq!(unbalanced.subroutines().count(), 1);
+
+        // A frame that moves the relative base past i128::MAX.
+        let words = vec![21101, 0, 7, 0, 1105, 1, 10, 99, 0, 0, 109, i128::MAX];
+        let words = [words, vec![109, i128::MAX, 2105, 1, 0]].concat();
+        let graph = ControlFlowGraph::new(&Disassembly::new(words));
+        assert!(!graph.subroutine_at(10).unwrap().balanced);
+    }
+
+    #[test]
+    fn test_computed_jumps() {
+        // Jumps through the pointer at 8 to code the disassembler can't see.
+        let words = parse_text::<i128>("5,7,8,99,104,1,99,1,4").unwrap();
+        let graph = ControlFlowGraph::new(&Disassembly::new(words.clone()));
+        assert_eq!(graph.computed_jumps(), [0]);
+        assert_eq!(
+            graph.block_at(0).unwrap().exit,
+            Exit::Branch(Target::Computed)
+        );
+        assert_eq!(
+            graph.successors(0).map(|edge| edge.to).collect::<Vec<_>>(),
+            [3]
+        );
+        assert!(graph.block_at(4).is_none());
+
+        let graph = ControlFlowGraph::new(&Disassembly::with_entry_points(words, &[0, 4]));
+        assert_eq!(graph.block_at(4).unwrap().exit, Exit::Halt);
+        assert_eq!(graph.predecessors(4).count(), 0);
+
+        // Calling through a function pointer still resumes at the return site.
+        let source = DOUBLE
+            .replace("JT #1, #double", "JT #1, [pointer]")
+            .replace("stack:  db 0, 0", "stack:  db 0, 0\npointer: db double");
+        let graph = analyse(&source);
+        assert_eq!(
+            graph.block_at(0).unwrap().exit,
+            Exit::Call {
+                entry: Target::Computed,
+                return_site: 11
+            }
+        );
+        assert!(graph.computed_jumps().is_empty());
+        assert_eq!(graph.subroutines().count(), 0);
+    }
+
+    #[test]
+    fn test_dot() {
+        let dot = analyse(DOUBLE).dot();
+        assert!(dot.starts_with("digraph intcode {\n"));
+        assert!(dot.contains("    subgraph cluster_14 {\n        label=\"sub_14, frame 1\";\n"));
+        assert!(dot.contains("b11 [label=\"11: OUT [25]\\l13: HALT\\l\"];"));
+        assert!(dot.contains("    b0 -> b14 [style=bold, label=\"call\"];\n"));
+        assert!(dot.contains("    b0 -> b11 [style=dashed];\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
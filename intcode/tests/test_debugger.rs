@@ -0,0 +1,167 @@
+use intcode::{assemble, Debugger, Program, Stop};
+use std::io::Cursor;
+
+#[cfg(test)]
+mod tests_debugger {
+    use super::*;
+
+    // Reads x, doubles it in a subroutine and prints it.
+    const DOUBLE: &str = "
+                ARB #stack          ; 0
+                IN [x]              ; 2
+                ADD #ret, #0, [rb]  ; 4
+                JT #1, #double      ; 8
+        ret:    OUT [x]             ; 11
+                HALT                ; 13
+        double: ARB #1              ; 14
+                MUL [x], #2, [x]    ; 16
+                ARB #-1             ; 20
+                JT #1, [rb]         ; 22
+        x:      db 0                ; 25
+        stack:  db 0, 0             ; 26
+    ";
+
+    fn debugger(inputs: &[i128]) -> Debugger {
+        Debugger::new(Program::new(&assemble(DOUBLE).unwrap(), inputs).unwrap())
+    }
+
+    #[test]
+    fn test_breakpoint_and_finish() {
+        let mut debugger = debugger(&[21]);
+        debugger.add_breakpoint(14);
+        assert_eq!(debugger.cont(), Ok(Stop::Breakpoint(14)));
+        assert_eq!(debugger.program().relative_base(), 26);
+
+        assert_eq!(debugger.step(), Ok(Stop::Step));
+        assert_eq!(debugger.program().ip(), 16);
+        assert_eq!(debugger.finish(), Ok(Stop::Returned));
+        assert_eq!(debugger.program().ip(), 11);
+
+        assert_eq!(debugger.cont(), Ok(Stop::Halted));
+        assert_eq!(debugger.outputs(), [42]);
+    }
+
+    #[test]
+    fn test_continue_moves_past_breakpoint() {
+        let mut debugger = debugger(&[1]);
+        debugger.add_breakpoint(11);
+        assert_eq!(debugger.cont(), Ok(Stop::Breakpoint(11)));
+        assert_eq!(debugger.cont(), Ok(Stop::Halted));
+        assert!(debugger.remove_breakpoint(11));
+        assert!(!debugger.remove_breakpoint(11));
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut debugger = debugger(&[5]);
+        debugger.add_watchpoint(25);
+        assert_eq!(
+            debugger.cont(),
+            Ok(Stop::Watchpoint {
+                ip: 2,
+                address: 25,
+                old: 0,
+                new: 5
+            })
+        );
+        assert_eq!(
+            debugger.cont(),
+            Ok(Stop::Watchpoint {
+                ip: 16,
+                address: 25,
+                old: 5,
+                new: 10
+            })
+        );
+        assert_eq!(debugger.cont(), Ok(Stop::Halted));
+    }
+
+    #[test]
+    fn test_waits_for_input() {
+        let mut debugger = debugger(&[]);
+        assert_eq!(debugger.cont(), Ok(Stop::NeedsInput));
+        assert_eq!(debugger.program().ip(), 2);
+        debugger.program_mut().send_input(4);
+        assert_eq!(debugger.cont(), Ok(Stop::Halted));
+        assert_eq!(debugger.outputs(), [8]);
+    }
+
+    #[test]
+    fn test_scripted_repl() {
+        let script = "
+            # comments and blank lines are skipped
+            break 14
+            continue
+            input 3
+            continue
+            info
+            x 25 2
+            step 2
+            finish
+            continue
+            bogus
+            quit
+            continue
+        ";
+        let mut debugger = debugger(&[]);
+        let mut output = vec![];
+        debugger.repl(Cursor::new(script), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let expected = [
+            "     0: ARB #26",
+            "Breakpoint set at 14",
+            "Waiting for input",
+            "     2: IN [25]",
+            "[3]",
+            "Breakpoint at 14",
+            "    14: ARB #1",
+            "ip 14, relative base 26",
+            "breakpoints {14}",
+            "watchpoints {}",
+            "    14: ARB #1",
+            "[25] 3 11",
+            "    20: ARB #-1",
+            "Returned",
+            "    11: OUT [25]",
+            "Output 6",
+            "Halted",
+            "    13: HALT",
+            "Unknown command \"bogus\", try help",
+        ];
+        assert_eq!(output.lines().collect::<Vec<&str>>(), expected);
+        assert_eq!(debugger.outputs(), [6]);
+    }
+
+    #[test]
+    fn test_addresses_past_the_end() {
+        let script = "
+            x 18446744073709551615 2
+            x 18446744073709551615
+            l 18446744073709551615 3
+            x 18446744073709551616
+        ";
+        let mut output = vec![];
+        debugger(&[])
+            .repl(Cursor::new(script), &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let expected = [
+            "     0: ARB #26",
+            "Not an address: 18446744073709551615 + 2",
+            "[18446744073709551615] 0",
+            "18446744073709551615: db 0",
+            "Not an address: 18446744073709551616",
+        ];
+        assert_eq!(output.lines().collect::<Vec<&str>>(), expected);
+
+        // Watching a write through a relative base that overflows.
+        let program = Program::new(
+            "109,170141183460469231731687303715884105727,21101,1,1,1,99",
+            &[],
+        );
+        let mut debugger = Debugger::new(program.unwrap());
+        debugger.add_watchpoint(0);
+        assert_eq!(debugger.step(), Ok(Stop::Step));
+        assert!(debugger.step().is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use intcode::{assemble_words, decompile, ControlFlowGraph, Disassembly};
+
+#[cfg(test)]
+mod tests_decompile {
+    use super::*;
+
+    fn lift(source: &str) -> String {
+        decompile(&ControlFlowGraph::new(&Disassembly::new(
+            assemble_words(source).unwrap(),
+        )))
+    }
+
+    #[test]
+    fn test_stack_frames() {
+        let source = "
+                    ARB #stack
+                    ADD #3, #0, [rb+1]
+                    ADD #4, #0, [rb+2]
+                    ADD #ret, #0, [rb]
+                    JT #1, #sum
+            ret:    OUT [rb+1]
+                    HALT
+            sum:    ARB #3
+                    ADD [rb-2], [rb-1], [rb-2]
+                    ARB #-3
+                    JT #1, [rb]
+            stack:  db 0
+        ";
+        let expected = "\
+void main() {
+    sub_20(3, 4);
+    output(arg1);
+    halt();
+}
+
+// frame 3, called from 14
+void sub_20(v1, v2) {
+    v1 = v1 + v2;
+    return;
+}
+";
+        assert_eq!(lift(source), expected);
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let source = "
+                    IN [n]
+            loop:   JF [n], #done
+                    OUT [n]
+                    ADD [n], #-1, [n]
+                    JT #1, #loop
+            done:   HALT
+            n:      db 0
+        ";
+        let expected = "\
+void main() {
+    mem[15] = input();
+    while (mem[15]) {
+        output(mem[15]);
+        mem[15] = mem[15] - 1;
+    }
+    halt();
+}
+";
+        assert_eq!(lift(source), expected);
+
+        let source = "
+                    IN [n]
+            loop:   OUT [n]
+                    ADD [n], #-1, [n]
+                    LT #0, [n], [flag]
+                    JT [flag], #loop
+                    HALT
+            n:      db 0
+            flag:   db 0
+        ";
+        let expected = "\
+void main() {
+    mem[16] = input();
+    do {
+        output(mem[16]);
+        mem[16] = mem[16] - 1;
+    } while (0 < mem[16]);
+    halt();
+}
+";
+        assert_eq!(lift(source), expected);
+    }
+
+    #[test]
+    fn test_compare_and_branch() {
+        let source = "
+                    IN [x]
+                    EQ [x], #1, [flag]
+                    JF [flag], #other
+                    OUT #10
+                    JT #1, #done
+            other:  LT [x], #5, [flag]
+                    JF [flag], #big
+                    OUT #20
+                    JT #1, #done
+            big:    OUT #30
+            done:   HALT
+            x:      db 0
+            flag:   db 0
+        ";
+        let expected = "\
+void main() {
+    mem[29] = input();
+    if (mem[29] == 1) {
+        output(10);
+    } else if (mem[29] < 5) {
+        output(20);
+    } else {
+        output(30);
+    }
+    halt();
+}
+";
+        assert_eq!(lift(source), expected);
+    }
+
+    #[test]
+    fn test_unstructured_jumps() {
+        // A loop that can be entered in two places.
+        let source = "
+                    IN [x]
+                    JT [x], #b
+            a:      OUT #1
+            b:      OUT #2
+                    IN [x]
+                    JT [x], #a
+                    HALT
+            x:      db 0
+        ";
+        let lifted = lift(source);
+        assert!(lifted.contains("\nL5:\n        output(1);\n"), "{}", lifted);
+        assert!(
+            lifted.contains("if (mem[15]) {\n        goto L5;\n    }"),
+            "{}",
+            lifted
+        );
+
+        // A jump through memory the disassembler can't follow.
+        assert_eq!(
+            lift("JT #1, [ptr]\nptr: db 3"),
+            "void main() {\n    goto *mem[3];\n}\n"
+        );
+
+        // A call into the middle of its own instruction, where no block starts.
+        assert_eq!(
+            lift("EQ [x], [x], [x]\nADD #6, #5, [x]\nJT #1, #1\nHALT\nx: db 0"),
+            "void main() {\n    mem[12] = mem[12] == mem[12];\n    sub_1();\n    halt();\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_overflowing_operands() {
+        let lift_words = |words| decompile(&ControlFlowGraph::new(&Disassembly::new(words)));
+        assert_eq!(
+            lift_words(vec![1001, 0, i128::MIN, 20, 99]),
+            format!(
+                "void main() {{\n    mem[20] = mem[0] + {};\n    halt();\n}}\n",
+                i128::MIN
+            )
+        );
+        assert_eq!(
+            lift_words(vec![109, i128::MAX, 109, i128::MAX, 99]),
+            "void main() {\n    halt();\n}\n"
+        );
+        assert_eq!(
+            lift_words(vec![109, i128::MIN, 204, -1, 99]),
+            "void main() {\n    output(rb[-1]);\n    halt();\n}\n"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use intcode::{disassemble, Disassembly, Flow, Instruction, Operand, Target};
+
+#[cfg(test)]
+mod tests_disasm {
+    use super::*;
+
+    #[test]
+    fn test_instruction() {
+        let instruction = Instruction::decode(&[21101, 5, -3, 12], 0).unwrap();
+        assert_eq!(instruction.to_string(), "ADD #5, #-3, [r+12]");
+        assert_eq!(instruction.len(), 4);
+        assert_eq!(instruction.write_operand(), Some(Operand::Relative(12)));
+
+        let instruction = Instruction::decode(&[0, 2205, -1, 7], 1).unwrap();
+        assert_eq!(instruction.to_string(), "JT [r-1], [r+7]");
+        assert_eq!(instruction.flow(), Flow::Branch(Target::Computed));
+        let past_the_end = usize::MAX as i128 + 1;
+        let instruction = Instruction::decode(&[1105, 1, past_the_end], 0).unwrap();
+        assert_eq!(instruction.flow(), Flow::Jump(Target::Computed));
+
+        let instruction = Instruction::decode(&[1101, i128::MAX, 1, 5], 0).unwrap();
+        assert_eq!(instruction.constant_result(), None);
+        assert!(disassemble("1101,170141183460469231731687303715884105727,1,5,99").is_ok());
+        let instruction = Instruction::decode(&[204, i128::MIN], 0).unwrap();
+        assert_eq!(
+            instruction.to_string(),
+            "OUT [r-170141183460469231731687303715884105728]"
+        );
+
+        assert_eq!(Instruction::decode(&[1, 2, 3], 0), None);
+        assert_eq!(Instruction::decode(&[42, 0, 0, 0], 0), None);
+        assert_eq!(Instruction::decode(&[301, 0, 0, 0], 0), None);
+    }
+
+    #[test]
+    fn test_listing() {
+        let listing = disassemble("1101,5,3,12,4,12,1105,1,11,7,8,99,0").unwrap();
+        assert_eq!(
+            listing.to_string(),
+            [
+                "     0: 1101 5 3 12                  ADD #5, #3, [12]",
+                "     4: 4 12                         OUT [12]",
+                "     6: 1105 1 11                    JT #1, #11",
+                "     9: 7 8                          db 7, 8",
+                "L11:",
+                "    11: 99                           HALT",
+                "    12: 0                            db 0",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_follows_jumps() {
+        // Branches over two data words to one of two outputs.
+        let listing = disassemble("3,14,1005,14,10,104,0,99,-5,-6,104,1,99,-7,0").unwrap();
+        assert!(listing.is_code(6));
+        assert!(listing.is_code(11));
+        assert!(!listing.is_code(8));
+        assert!(!listing.is_code(13));
+        assert!(listing.is_jump_target(10));
+        assert_eq!(
+            listing
+                .instructions()
+                .map(|i| i.address)
+                .collect::<Vec<usize>>(),
+            [0, 2, 5, 7, 10, 12]
+        );
+    }
+
+    #[test]
+    fn test_follows_calls() {
+        // Stores a return address, calls a routine at 10 that returns through [r+0].
+        let words = vec![109, 20, 21101, 9, 0, 0, 1105, 1, 10, 99, 104, 1, 2106, 0, 0];
+        let listing = Disassembly::new(words.clone());
+        assert!(listing.is_code(9));
+        assert_eq!(listing.instruction_at(9).unwrap().mnemonic(), "HALT");
+
+        let listing = Disassembly::with_entry_points(words, &[]);
+        assert_eq!(listing.instructions().count(), 0);
+    }
+}
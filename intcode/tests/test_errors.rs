@@ -0,0 +1,105 @@
+use intcode::{process_program, AccessPolicy, IntcodeError, Program};
+
+#[cfg(test)]
+mod tests_errors {
+    use super::*;
+
+    #[test]
+    fn test_parse_error() {
+        let result = Program::new("1,0,x,0,99", &[]);
+        assert_eq!(
+            result.err(),
+            Some(IntcodeError::Parse {
+                index: 2,
+                value: String::from("x")
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_opcode() {
+        let result = process_program("1,0,0,0,42,99", &[]);
+        assert_eq!(
+            result.err(),
+            Some(IntcodeError::InvalidOpcode { ip: 4, opcode: 42 })
+        );
+    }
+
+    #[test]
+    fn test_invalid_mode() {
+        let result = process_program("301,0,0,0,99", &[]);
+        assert_eq!(
+            result.err(),
+            Some(IntcodeError::InvalidMode {
+                ip: 0,
+                opcode: 301,
+                mode: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_mode_past_last_parameter() {
+        // OUT takes one parameter, so the hundreds-of-thousands digit has nothing to describe.
+        let result = process_program("100104,0,99", &[]);
+        assert_eq!(
+            result.err(),
+            Some(IntcodeError::InvalidMode {
+                ip: 0,
+                opcode: 100104,
+                mode: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_missing_input() {
+        let result = process_program("3,0,99", &[]);
+        assert_eq!(
+            result.err(),
+            Some(IntcodeError::MissingInput { ip: 0, opcode: 3 })
+        );
+    }
+
+    #[test]
+    fn test_negative_address() {
+        let result = process_program("1,-1,0,0,99", &[]);
+        assert_eq!(
+            result.err(),
+            Some(IntcodeError::NegativeAddress {
+                ip: 0,
+                opcode: 1,
+                address: -1
+            })
+        );
+
+        let result = process_program("1105,1,-4,99", &[]);
+        assert_eq!(
+            result.err(),
+            Some(IntcodeError::NegativeAddress {
+                ip: 0,
+                opcode: 1105,
+                address: -4
+            })
+        );
+    }
+
+    #[test]
+    fn test_end_of_address_space() {
+        // Writes an ADD just below usize::MAX and jumps to it; its operands don't fit.
+        let end = usize::MAX as i128 - 2;
+        let words = vec![1101, 1100, 1, end, 1105, 1, end];
+        let mut program = Program::from_cells(words.clone(), &[]);
+        let overflow = IntcodeError::Overflow {
+            ip: usize::MAX - 2,
+            opcode: 1101,
+        };
+        assert_eq!(program.run(), Err(overflow.clone()));
+
+        let mut program = Program::from_cells(words, &[])
+            .with_access_policy(AccessPolicy::new())
+            .with_history(10)
+            .with_profiler();
+        assert_eq!(program.run(), Err(overflow));
+    }
+}
@@ -0,0 +1,70 @@
+use intcode::{
+    decode_binary, encode_binary, parse_text, read_program, IntcodeError, Memory, Program,
+    VecMemory,
+};
+use std::env;
+use std::fs;
+
+#[cfg(test)]
+mod tests_format {
+    use super::*;
+
+    #[test]
+    fn test_annotated_text() {
+        let text = "# Outputs its input\n3,0,  # IN [0]\n4,0\n\n99 # HALT\n";
+        assert_eq!(parse_text::<i128>(text), Ok(vec![3, 0, 4, 0, 99]));
+        assert_eq!(parse_text::<i64>("1 2\t-3,\r\n4,"), Ok(vec![1, 2, -3, 4]));
+        assert_eq!(
+            parse_text::<i128>("1,2\n x # y"),
+            Err(IntcodeError::Parse {
+                index: 2,
+                value: String::from("x")
+            })
+        );
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let cells = vec![0, 1, -1, 63, -64, 64, 1_000_000, i128::MIN, i128::MAX];
+        let bytes = encode_binary(&cells).unwrap();
+        assert_eq!(decode_binary::<i128>(&bytes), Ok(cells));
+
+        // Magic, count, then one byte per small value.
+        let small = encode_binary(&[1i64, -1, 99]).unwrap();
+        assert_eq!(&small[5..], &[3, 2, 1, 198, 1]);
+
+        assert!(decode_binary::<i128>(b"3,0,4,0,99").is_err());
+        assert!(decode_binary::<i128>(&bytes[..bytes.len() - 1]).is_err());
+        let too_big = encode_binary(&[i128::from(i64::MAX) + 1]).unwrap();
+        assert_eq!(
+            decode_binary::<i64>(&too_big),
+            Err(IntcodeError::Binary {
+                position: 6,
+                message: String::from("9223372036854775808 does not fit in a cell")
+            })
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        let dir = env::temp_dir().join(format!("intcode-format-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let text = dir.join("echo.int");
+        fs::write(&text, "3,0,4,0 # echo\n99\n").unwrap();
+
+        let mut program = Program::from_path(&text, &[5]).unwrap();
+        assert_eq!(program.run(), Ok(vec![5]));
+        let dump = dir.join("echo.bin");
+        program.dump_memory(&dump).unwrap();
+        assert_eq!(read_program::<i128>(&dump), Ok(vec![5, 0, 4, 0, 99]));
+
+        let program = Program::<_, _, VecMemory<i32>>::from_path(&dump, &[]).unwrap();
+        assert_eq!(program.memory().to_vec(), [5, 0, 4, 0, 99]);
+
+        assert!(matches!(
+            read_program::<i128>(dir.join("missing.int")),
+            Err(IntcodeError::File { .. })
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
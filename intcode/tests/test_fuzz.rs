@@ -0,0 +1,98 @@
+use intcode::{assemble_words, FuzzCase, Fuzzer, Limits, Program, Property};
+
+#[cfg(test)]
+mod tests_fuzz {
+    use super::*;
+
+    fn outputs(case: &FuzzCase) -> Vec<i128> {
+        Program::from_cells(case.words(), case.inputs())
+            .with_limits(Limits::new().steps(1000).memory(4096))
+            .run()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_properties_hold() {
+        let passed = Fuzzer::new().seed(7).cases(300).run();
+        assert_eq!(passed.map_err(|failure| failure.to_string()), Ok(300));
+    }
+
+    #[test]
+    fn test_generate() {
+        let fuzzer = Fuzzer::new().max_len(5);
+        let case = fuzzer.generate(42);
+        assert_eq!(case, fuzzer.generate(42));
+        assert_ne!(case, fuzzer.generate(43));
+        assert_eq!(case.words(), assemble_words(&case.to_string()).unwrap());
+
+        for seed in 0..50 {
+            let source = fuzzer.generate(seed).to_string();
+            let lines = source.lines().collect::<Vec<&str>>();
+            assert!(lines[0].starts_with("; inputs:"));
+            assert!(lines.len() <= 8, "{}", source);
+            assert!(lines[lines.len() - 2].ends_with(": HALT"));
+            assert!(lines[lines.len() - 1].starts_with("data: db "));
+        }
+    }
+
+    #[test]
+    fn test_far_jumps() {
+        // Each stores an opcode just below usize::MAX and jumps to it, whose operands
+        // then run past the end of the address space.
+        let fuzzer = Fuzzer::new();
+        for &seed in [2348, 100176].iter() {
+            let case = fuzzer.generate(seed);
+            assert!(
+                case.to_string().contains(&format!("[{}]", usize::MAX)),
+                "{}",
+                case
+            );
+            assert_eq!(fuzzer.check(&case), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_shrinking() {
+        let fuzzer = Fuzzer::new().property("small outputs", |case| {
+            match outputs(case).iter().find(|&&output| output >= 5) {
+                Some(output) => Err(format!("output {}", output)),
+                None => Ok(()),
+            }
+        });
+        let failure = fuzzer.run().unwrap_err();
+        assert_eq!(
+            failure.property,
+            Property::Custom("small outputs".to_string())
+        );
+        assert_eq!(fuzzer.generate(failure.seed), failure.original);
+
+        // Down to a single OUT of a value from 5 to 9, and nothing else.
+        let source = failure.shrunk.to_string();
+        let lines = source.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "; inputs:");
+        assert!(lines[1].starts_with("l0: OUT "), "{}", source);
+        assert_eq!(lines[2], "l1: HALT");
+        assert!(fuzzer.check(&failure.shrunk).is_err());
+        assert!(failure.to_string().starts_with(&format!(
+            "Case {} breaks small outputs: output ",
+            failure.seed
+        )));
+    }
+
+    #[test]
+    fn test_panics_are_caught() {
+        let fuzzer = Fuzzer::new().property("quiet", |case| {
+            if !case.inputs().is_empty() {
+                panic!("read {} inputs", case.inputs().len());
+            }
+            Ok(())
+        });
+        let failure = fuzzer.run().unwrap_err();
+        assert_eq!(failure.property, Property::NoPanic);
+        assert_eq!(failure.message, "checking quiet panicked: read 1 inputs");
+        assert_eq!(
+            failure.shrunk.to_string(),
+            "; inputs: 0\nl0: HALT\ndata: db 0, 0, 0, 0, 0, 0, 0, 0\n"
+        );
+    }
+}
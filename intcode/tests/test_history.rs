@@ -0,0 +1,138 @@
+use intcode::{AccessPolicy, Debugger, Memory, Policy, Program, StepResult};
+use std::io::Cursor;
+
+#[cfg(test)]
+mod tests_history {
+    use super::*;
+
+    // Reads x, adds 5 to it in place, outputs it and halts.
+    const ADD_FIVE: &str = "3,9,1001,9,5,9,4,9,99,0";
+
+    #[test]
+    fn test_step_back() {
+        let mut program = Program::new("109,7,21101,2,3,0,99,0", &[])
+            .unwrap()
+            .with_history(10);
+        assert_eq!(program.run(), Ok(vec![]));
+        assert_eq!(program.memory().get(7), 5);
+        assert_eq!(program.history_len(), 2);
+
+        assert_eq!(program.step_back(), Some(StepResult::Continued));
+        assert_eq!(program.memory().get(7), 0);
+        assert_eq!(program.ip(), 2);
+        assert_eq!(program.relative_base(), 7);
+
+        assert_eq!(program.step_back(), Some(StepResult::Continued));
+        assert_eq!(program.ip(), 0);
+        assert_eq!(program.relative_base(), 0);
+        assert_eq!(program.step_back(), None);
+
+        assert!(Program::new("99", &[]).unwrap().step_back().is_none());
+    }
+
+    #[test]
+    fn test_trapped_steps_are_not_logged() {
+        // Sets the relative base, then jumps through the pointer at 10 into data.
+        let mut program = Program::new("109,3,6,9,10,99,104,1,99,0,6", &[])
+            .unwrap()
+            .with_access_policy(AccessPolicy::all(Policy::Trap))
+            .with_history(10);
+        assert!(program.run().is_err());
+        assert_eq!(program.ip(), 2);
+        assert_eq!(program.history_len(), 1);
+
+        assert_eq!(program.step_back(), Some(StepResult::Continued));
+        assert_eq!(program.ip(), 0);
+        assert_eq!(program.relative_base(), 0);
+        assert_eq!(program.step_back(), None);
+    }
+
+    #[test]
+    fn test_inputs_are_read_again() {
+        let mut program = Program::new(ADD_FIVE, &[3]).unwrap().with_history(10);
+        assert_eq!(program.run(), Ok(vec![8]));
+
+        assert_eq!(program.checkpoint().unwrap().steps(), 3);
+
+        for _ in 0..2 {
+            program.step_back();
+        }
+        assert_eq!(program.ip(), 2);
+        assert_eq!(program.step_back(), Some(StepResult::Continued));
+        assert_eq!(program.memory().get(9), 0);
+        assert_eq!(program.checkpoint().unwrap().steps(), 0);
+
+        // The 3 was already taken off the input queue, but is read again first.
+        program.send_input(10);
+        assert_eq!(program.run(), Ok(vec![8]));
+        assert_eq!(program.run(), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_rewind_within_capacity() {
+        let mut program = Program::new(ADD_FIVE, &[]).unwrap().with_history(2);
+        let start = program.checkpoint().unwrap();
+        program.send_input(3);
+        let middle = {
+            program.execute().unwrap();
+            program.checkpoint().unwrap()
+        };
+        assert_eq!(program.run(), Ok(vec![8]));
+        assert_eq!(program.history_len(), 2);
+
+        assert_eq!(program.rewind(start), None);
+        assert_eq!(program.rewind(middle), Some(vec![8]));
+        assert_eq!(program.ip(), 2);
+        assert_eq!(program.memory().get(9), 3);
+        assert_eq!(program.run(), Ok(vec![8]));
+    }
+
+    #[test]
+    fn test_restore_forgets_checkpoints() {
+        let mut program = Program::new(ADD_FIVE, &[3, 4]).unwrap().with_history(10);
+        let snapshot = program.snapshot();
+        program.execute().unwrap();
+        let checkpoint = program.checkpoint().unwrap();
+
+        program.restore(&snapshot);
+        program.execute().unwrap();
+        assert_eq!(program.checkpoint().unwrap().steps(), 1);
+        assert_eq!(program.rewind(checkpoint), None);
+        assert_eq!(program.memory().get(9), 4);
+    }
+
+    #[test]
+    fn test_debugger_commands() {
+        let program = Program::new(ADD_FIVE, &[3]).unwrap().with_history(10);
+        let mut debugger = Debugger::new(program);
+        let script = "
+            checkpoint
+            continue
+            rwrite 9
+            rstep 5
+            rewind 0
+            rewind 1
+            continue
+        ";
+        let mut output = vec![];
+        debugger.repl(Cursor::new(script), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let expected = [
+            "     0: IN [9]",
+            "Checkpoint 0 at step 0",
+            "Output 8",
+            "Halted",
+            "     8: HALT",
+            "Last write to [9]",
+            "     2: ADD [9], #5, [9]",
+            "     0: IN [9]",
+            "     0: IN [9]",
+            "No checkpoint 1",
+            "Output 8",
+            "Halted",
+            "     8: HALT",
+        ];
+        assert_eq!(output.lines().collect::<Vec<&str>>(), expected);
+        assert_eq!(debugger.outputs(), [8]);
+    }
+}
@@ -0,0 +1,79 @@
+use intcode::{FnInput, FnOutput, IterInput, Pipe, Program, StepResult};
+use std::sync::mpsc;
+
+#[cfg(test)]
+mod tests_io {
+    use super::*;
+
+    // Reads a number and outputs it doubled, until it reads 0.
+    const DOUBLER: &str = "3,15,1006,15,14,1002,15,2,15,4,15,1105,1,0,99,0";
+
+    #[test]
+    fn test_iter_input_vec_output() {
+        let mut program = Program::new(DOUBLER, &[])
+            .unwrap()
+            .with_input(IterInput(vec![1, 2, 3].into_iter()))
+            .with_output(vec![]);
+        let (outputs, state) = program.run_until_blocked_or_done().unwrap();
+        assert_eq!(outputs, [2, 4, 6]);
+        assert_eq!(state, StepResult::NeedsInput);
+        assert_eq!(program.output(), &vec![2, 4, 6]);
+    }
+
+    #[test]
+    fn test_channels() {
+        let (input_tx, input_rx) = mpsc::channel();
+        let (output_tx, output_rx) = mpsc::channel();
+        let mut program = Program::new(DOUBLER, &[])
+            .unwrap()
+            .with_input(input_rx)
+            .with_output(output_tx);
+        input_tx.send(5).unwrap();
+        input_tx.send(0).unwrap();
+        let (_outputs, state) = program.run_until_blocked_or_done().unwrap();
+        assert_eq!(state, StepResult::Halted);
+        assert_eq!(output_rx.try_iter().collect::<Vec<i128>>(), [10]);
+    }
+
+    #[test]
+    fn test_closures() {
+        let mut next = 3;
+        let mut seen = vec![];
+        {
+            let mut program = Program::new(DOUBLER, &[])
+                .unwrap()
+                .with_input(FnInput(|| {
+                    next -= 1;
+                    Some(next)
+                }))
+                .with_output(FnOutput(|o| seen.push(o)));
+            program.run().unwrap();
+        }
+        assert_eq!(seen, [4, 2]);
+    }
+
+    #[test]
+    fn test_pipe_between_programs() {
+        let pipe = Pipe::new();
+        let mut first = Program::new(DOUBLER, &[7, 0])
+            .unwrap()
+            .with_output(pipe.clone());
+        let mut second = Program::new(DOUBLER, &[]).unwrap().with_input(pipe.clone());
+
+        first.run().unwrap();
+        assert_eq!(pipe.len(), 1);
+        let (outputs, state) = second.run_until_blocked_or_done().unwrap();
+        assert_eq!(outputs, [28]);
+        assert_eq!(state, StepResult::NeedsInput);
+        assert!(pipe.is_empty());
+    }
+
+    #[test]
+    fn test_consumed_inputs_are_dropped() {
+        let mut program = Program::new(DOUBLER, &[1, 2]).unwrap();
+        assert_eq!(program.num_inputs(), 2);
+        program.run_until_outputs(1).unwrap();
+        assert_eq!(program.num_inputs(), 1);
+        assert_eq!(program.input().len(), 1);
+    }
+}
@@ -0,0 +1,80 @@
+use intcode::{FnInput, IntcodeError, Limit, Limits, Memory, Program};
+
+#[cfg(test)]
+mod tests_limits {
+    use super::*;
+
+    const QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+    // Reads until it gets something other than -1, then halts.
+    const POLLER: &str = "3,12,1008,12,-1,13,1005,13,0,99,0,0,0,0";
+
+    #[test]
+    fn test_step_budget() {
+        let mut program = Program::new("1105,1,0", &[])
+            .unwrap()
+            .with_limits(Limits::new().steps(100));
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::LimitExceeded {
+                ip: 0,
+                limit: Limit::Steps(100)
+            })
+        );
+        assert_eq!(program.steps_taken(), Some(100));
+
+        // Halting on the last allowed step is fine.
+        let mut program = Program::new("1101,1,1,5,99", &[])
+            .unwrap()
+            .with_limits(Limits::new().steps(1));
+        assert_eq!(program.run(), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_input_that_never_arrives() {
+        let mut program = Program::new(POLLER, &[])
+            .unwrap()
+            .with_input(FnInput(|| Some(-1)))
+            .with_limits(Limits::new().steps(1000));
+        let snapshot = program.snapshot();
+        assert!(matches!(
+            program.run_until_blocked_or_done(),
+            Err(IntcodeError::LimitExceeded {
+                limit: Limit::Steps(1000),
+                ..
+            })
+        ));
+        program.restore(&snapshot);
+        assert_eq!(program.steps_taken(), Some(0));
+    }
+
+    #[test]
+    fn test_memory_cap() {
+        let mut program = Program::new("1101,1,1,1000000,99", &[])
+            .unwrap()
+            .with_limits(Limits::new().memory(1000));
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::LimitExceeded {
+                ip: 0,
+                limit: Limit::Memory(1000000)
+            })
+        );
+        assert_eq!(program.memory().len(), 5);
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let mut program = Program::new(QUINE, &[])
+            .unwrap()
+            .with_output(vec![])
+            .with_limits(Limits::new().outputs(3));
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::LimitExceeded {
+                ip: 2,
+                limit: Limit::Outputs(3)
+            })
+        );
+        assert_eq!(program.output(), &vec![109, 1, 204]);
+    }
+}
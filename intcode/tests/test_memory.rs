@@ -0,0 +1,76 @@
+use intcode::{
+    process_program, AccessPolicy, HashMemory, IntcodeError, Memory, PagedMemory, Policy, Program,
+    VecMemory,
+};
+use std::env;
+
+#[cfg(test)]
+mod tests_memory {
+    use super::*;
+
+    #[test]
+    fn test_vec_memory_grows() {
+        let mut memory = VecMemory::from_cells(vec![1, 2]);
+        assert_eq!(memory.get(5), 0);
+        assert_eq!(memory.len(), 2);
+        memory.set(5, 7);
+        assert_eq!(memory.to_vec(), [1, 2, 0, 0, 0, 7]);
+    }
+
+    #[test]
+    fn test_far_writes_stay_sparse() {
+        let far = 100_000_000_000_000;
+        let (program, _) = process_program("1101,1,1,100000000000000,99", &[]).unwrap();
+        assert_eq!(program.memory().get(far), 2);
+        assert_eq!(program.memory().to_vec(), [1101, 1, 1, far as i128, 99]);
+        assert_eq!(program.memory().sparse_cells(), [(far, 2)]);
+
+        let program = program.with_memory::<HashMemory>();
+        assert_eq!(program.memory().len(), 5);
+        assert_eq!(program.memory().sparse_cells(), [(far, 2)]);
+        let program = program
+            .with_memory::<PagedMemory>()
+            .with_access_policy(AccessPolicy::all(Policy::Trap));
+        assert_eq!(program.memory().len(), 5);
+        assert_eq!(program.memory().sparse_cells(), [(far, 2)]);
+        let path = env::temp_dir().join("intcode_test_far_writes.bin");
+        assert!(matches!(
+            program.dump_memory(&path),
+            Err(IntcodeError::Binary { position, .. }) if position == far
+        ));
+
+        let mut memory = PagedMemory::from_cells(vec![1, 2]);
+        memory.set(usize::MAX, 7);
+        memory.set(3, 4);
+        assert_eq!(memory.get(usize::MAX), 7);
+        assert_eq!(memory.get(usize::MAX - 1), 0);
+        assert_eq!(memory.get(3), 4);
+        assert_eq!(memory.len(), 4);
+        assert_eq!(memory.shared_pages(&memory.clone()), 2);
+    }
+
+    #[test]
+    fn test_hash_memory() {
+        let mut memory = HashMemory::from_cells(vec![1, 2]);
+        memory.set(5, 7);
+        assert_eq!(memory.get(4), 0);
+        assert_eq!(memory.to_vec(), [1, 2, 0, 0, 0, 7]);
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        let program_str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let (vec_program, vec_outputs) = process_program(program_str, &[]).unwrap();
+
+        let mut hash_program = Program::new(program_str, &[])
+            .unwrap()
+            .with_memory::<HashMemory>();
+        let hash_outputs = hash_program.run().unwrap();
+
+        assert_eq!(vec_outputs, hash_outputs);
+        assert_eq!(
+            vec_program.memory().to_vec(),
+            hash_program.memory().to_vec()
+        );
+    }
+}
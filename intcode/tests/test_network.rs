@@ -0,0 +1,85 @@
+use intcode::{Event, Message, Network, Packets, Pipeline, Program, Ring};
+
+#[cfg(test)]
+mod tests_network {
+    use super::*;
+
+    const AMPLIFIER: &str = "3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0";
+    const FEEDBACK: &str =
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+    // Reads until it gets something other than -1, then halts.
+    const POLLER: &str = "3,12,1008,12,-1,13,1005,13,0,99,0,0,0,0";
+
+    fn amps(program: &str, phases: &[i128]) -> Vec<Program> {
+        phases
+            .iter()
+            .map(|&phase| Program::new(program, &[phase]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let mut network = Network::new(amps(AMPLIFIER, &[4, 3, 2, 1, 0]), Pipeline);
+        network.send(0, &[0]);
+        assert_eq!(
+            network.next_event(),
+            Ok(Event::Message(Message {
+                from: 4,
+                to: 5,
+                values: vec![43210]
+            }))
+        );
+        assert_eq!(network.next_event(), Ok(Event::Halted));
+    }
+
+    #[test]
+    fn test_ring() {
+        let mut network = Network::new(amps(FEEDBACK, &[9, 8, 7, 6, 5]), Ring);
+        network.send(0, &[0]);
+        assert_eq!(network.next_event(), Ok(Event::Halted));
+        assert_eq!(network.last_output(4), Some(&139629729));
+    }
+
+    #[test]
+    fn test_packets() {
+        let machines = vec![
+            Program::new("104,1,104,42,104,43,99", &[]).unwrap(),
+            Program::new("3,100,3,101,104,255,4,100,4,101,99", &[]).unwrap(),
+        ];
+        let mut network = Network::new(machines, Packets::new(3));
+        assert_eq!(
+            network.next_event(),
+            Ok(Event::Message(Message {
+                from: 1,
+                to: 255,
+                values: vec![42, 43]
+            }))
+        );
+        assert_eq!(network.next_event(), Ok(Event::Halted));
+    }
+
+    #[test]
+    #[should_panic(expected = "packet size must be at least 1, got 0")]
+    fn test_empty_packets() {
+        Packets::<i128>::new(0);
+    }
+
+    #[test]
+    fn test_idle() {
+        // Blocked on input with nothing in flight.
+        let mut network = Network::new(amps(AMPLIFIER, &[0, 0]), Pipeline);
+        assert_eq!(network.next_event(), Ok(Event::Idle));
+        network.send(0, &[1]);
+        assert!(matches!(network.next_event(), Ok(Event::Message(_))));
+
+        // Polling with the idle input.
+        let machines = vec![Program::new(POLLER, &[]).unwrap(); 3];
+        let mut network = Network::new(machines, Pipeline).with_idle_input(-1);
+        assert_eq!(network.next_event(), Ok(Event::Idle));
+        assert_eq!(network.next_event(), Ok(Event::Idle));
+        for machine in 0..3 {
+            network.send(machine, &[7]);
+        }
+        assert_eq!(network.next_event(), Ok(Event::Halted));
+    }
+}
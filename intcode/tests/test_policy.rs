@@ -0,0 +1,128 @@
+use intcode::{
+    Access, AccessPolicy, Action, IntcodeError, Memory, OpcodeRegistry, Param, Policy, Program,
+    Violation,
+};
+
+#[cfg(test)]
+mod tests_policy {
+    use super::*;
+
+    // Writes 99 into the operand of the OUT at 4, then outputs it.
+    const SELF_MODIFYING: &str = "1101,98,1,5,104,0,99";
+    // Jumps through the pointer at 8 to code the disassembler can't see.
+    const COMPUTED_JUMP: &str = "5,7,8,99,104,1,99,1,4";
+
+    #[test]
+    fn test_code_writes() {
+        let policy = AccessPolicy::new().code_writes(Policy::Warn);
+        let mut program = Program::new(SELF_MODIFYING, &[])
+            .unwrap()
+            .with_access_policy(policy);
+        assert_eq!(program.run(), Ok(vec![99]));
+        assert_eq!(
+            program.violations(),
+            [Violation {
+                access: Access::CodeWrite,
+                ip: 0,
+                address: 5
+            }]
+        );
+
+        let policy = AccessPolicy::new().code_writes(Policy::Trap);
+        let mut program = Program::new(SELF_MODIFYING, &[])
+            .unwrap()
+            .with_access_policy(policy);
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::AccessViolation {
+                ip: 0,
+                address: 5,
+                access: Access::CodeWrite
+            })
+        );
+        assert_eq!(program.memory().get(5), 0);
+    }
+
+    #[test]
+    fn test_uninitialized_reads() {
+        let policy = AccessPolicy::all(Policy::Trap);
+        let mut program = Program::new("4,100,99", &[])
+            .unwrap()
+            .with_access_policy(policy);
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::AccessViolation {
+                ip: 0,
+                address: 100,
+                access: Access::UninitializedRead
+            })
+        );
+
+        let mut program = Program::new("1101,1,1,100,4,100,99", &[])
+            .unwrap()
+            .with_access_policy(policy);
+        assert_eq!(program.run(), Ok(vec![2]));
+    }
+
+    #[test]
+    fn test_data_jumps() {
+        let policy = AccessPolicy::new().data_jumps(Policy::Warn);
+        let mut program = Program::new(COMPUTED_JUMP, &[])
+            .unwrap()
+            .with_access_policy(policy);
+        assert_eq!(program.run(), Ok(vec![1]));
+        assert_eq!(program.violations().len(), 1);
+        assert_eq!(
+            program.violations()[0].to_string(),
+            "0: Jump into data at 4"
+        );
+
+        let mut program = Program::new(COMPUTED_JUMP, &[])
+            .unwrap()
+            .with_access_policy(AccessPolicy::all(Policy::Trap))
+            .with_profiler();
+        assert!(program.run().is_err());
+        assert_eq!(program.ip(), 0);
+        assert_eq!(program.profile().unwrap().instructions(), 0);
+    }
+
+    #[test]
+    fn test_custom_jumps() {
+        // Opcode 50 stores 7 through its parameter, then jumps to the data at 3.
+        let mut registry = OpcodeRegistry::new();
+        registry
+            .register(50, "STJ", &[Param::Write], |call| {
+                call.write(0, 7);
+                Ok(Action::Jump(3))
+            })
+            .unwrap();
+        let mut program = Program::new("50,4,99,0,0", &[])
+            .unwrap()
+            .with_opcodes(registry)
+            .with_access_policy(AccessPolicy::all(Policy::Trap))
+            .with_history(10)
+            .with_profiler();
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::AccessViolation {
+                ip: 0,
+                address: 3,
+                access: Access::DataJump
+            })
+        );
+        assert_eq!(program.ip(), 0);
+        assert_eq!(program.memory().get(4), 0);
+        assert_eq!(program.history_len(), 0);
+        assert_eq!(program.profile().unwrap().instructions(), 0);
+    }
+
+    #[test]
+    fn test_allowed_by_default() {
+        let mut program = Program::new(SELF_MODIFYING, &[])
+            .unwrap()
+            .with_access_policy(AccessPolicy::new());
+        assert_eq!(program.run(), Ok(vec![99]));
+        assert!(program.violations().is_empty());
+        assert!(Program::new("99", &[]).unwrap().violations().is_empty());
+    }
+}
@@ -0,0 +1,32 @@
+use intcode::{process_program, Memory, Program};
+
+#[cfg(test)]
+mod tests_predecode {
+    use super::*;
+
+    #[test]
+    fn test_rewritten_opcode_is_decoded_again() {
+        // The HALT at 4 is overwritten with MUL before execution reaches it.
+        let (program, _) = process_program("1,1,1,4,99,5,6,0,99", &[]).unwrap();
+        assert_eq!(program.memory().to_vec(), [30, 1, 1, 4, 2, 5, 6, 0, 99]);
+    }
+
+    #[test]
+    fn test_rewritten_modes_are_decoded_again() {
+        // OUT [9] prints 42, then the ADD turns it into OUT #9 and jumps back to it.
+        let mut program = Program::new("4,9,1101,100,4,0,1105,1,0,42", &[]).unwrap();
+        let (outputs, _) = program.run_until_outputs(2).unwrap();
+        assert_eq!(outputs, [42, 9]);
+    }
+
+    #[test]
+    fn test_clones_decode_their_own_memory() {
+        let mut original = Program::new("1101,1,1,5,99,0", &[]).unwrap();
+        let mut clone = original.clone();
+        clone.set(0, 1102);
+        clone.run().unwrap();
+        original.run().unwrap();
+        assert_eq!(clone.memory().to_vec(), [1102, 1, 1, 5, 99, 1]);
+        assert_eq!(original.memory().to_vec(), [1101, 1, 1, 5, 99, 2]);
+    }
+}
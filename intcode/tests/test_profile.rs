@@ -0,0 +1,114 @@
+use intcode::{assemble, Action, Loop, MemoryTouches, OpcodeRegistry, Program, StepResult};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests_profile {
+    use super::*;
+
+    const COUNT_TO_3: &str = "
+        loop:   ADD [i], #1, [i]    ; 0
+                LT [i], #3, [t]     ; 4
+                JT [t], #loop       ; 8
+                OUT [i]             ; 11
+                HALT                ; 13
+        i:      db 0                ; 14
+        t:      db 0                ; 15
+    ";
+
+    #[test]
+    fn test_counts_executions_and_memory() {
+        let mut program = Program::new(&assemble(COUNT_TO_3).unwrap(), &[])
+            .unwrap()
+            .with_profiler();
+        assert_eq!(program.run().unwrap(), [3]);
+        let profile = program.take_profile().unwrap();
+        assert!(program.profile().is_none());
+
+        assert_eq!(profile.instructions(), 11);
+        assert_eq!(profile.executions(0), 3);
+        assert_eq!(profile.executions(11), 1);
+        assert_eq!(profile.executions(14), 0);
+        assert_eq!(profile.opcode_count("JT"), 3);
+        assert_eq!(profile.opcode_count("MUL"), 0);
+        assert_eq!(
+            profile.memory_touches(14),
+            MemoryTouches {
+                reads: 7,
+                writes: 3
+            }
+        );
+        assert_eq!(
+            profile.memory_touches(15),
+            MemoryTouches {
+                reads: 3,
+                writes: 3
+            }
+        );
+        assert_eq!(
+            profile.loops(),
+            [Loop {
+                head: 0,
+                tail: 8,
+                iterations: 2
+            }]
+        );
+        assert_eq!(profile.hot_spots(2), [(0, "ADD", 3), (4, "LT", 3)]);
+    }
+
+    #[test]
+    fn test_input_stalls() {
+        let mut program = Program::new("3,5,4,5,99,0", &[]).unwrap().with_profiler();
+        assert_eq!(program.execute().unwrap(), StepResult::NeedsInput);
+        assert_eq!(program.execute().unwrap(), StepResult::NeedsInput);
+        thread::sleep(Duration::from_millis(5));
+        program.send_input(7);
+        assert_eq!(program.run().unwrap(), [7]);
+
+        let profile = program.profile().unwrap();
+        assert_eq!(profile.input_stalls(), 2);
+        assert_eq!(profile.opcode_count("IN"), 1);
+        assert!(profile.input_wait() >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_reports() {
+        let mut program = Program::new(&assemble(COUNT_TO_3).unwrap(), &[])
+            .unwrap()
+            .with_profiler();
+        program.run().unwrap();
+        let profile = program.profile().unwrap();
+
+        let report = profile.report(1);
+        assert!(report.starts_with("Instructions: 11\n"));
+        assert!(report.contains("\nHot spots:\n       0: ADD               3  27.27%\n"));
+        assert!(report.contains("\nLoops:\n       0 <- 8                 2 iterations\n"));
+
+        let json = profile.to_json(1);
+        assert!(json.starts_with("{\"instructions\":11,\"input_stalls\":0,"));
+        assert!(
+            json.contains("\"hot_spots\":[{\"address\":0,\"mnemonic\":\"ADD\",\"executions\":3}]")
+        );
+        assert!(json.contains("\"loops\":[{\"head\":0,\"tail\":8,\"iterations\":2}]"));
+        assert!(json.contains("\"memory\":[{\"address\":14,\"reads\":7,\"writes\":3}]"));
+    }
+
+    #[test]
+    fn test_json_escapes_mnemonics() {
+        let mnemonic = "SAY \"HI\" \\ BYE\n";
+        let mut registry = OpcodeRegistry::new();
+        registry
+            .register(50, mnemonic, &[], |_| Ok(Action::Continue))
+            .unwrap();
+        let mut program = Program::new("50,50,99", &[])
+            .unwrap()
+            .with_opcodes(registry)
+            .with_profiler();
+        program.run().unwrap();
+
+        let json = program.profile().unwrap().to_json(1);
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["opcodes"][mnemonic], 2);
+        assert_eq!(parsed["hot_spots"][0]["mnemonic"], mnemonic);
+    }
+}
@@ -0,0 +1,99 @@
+use intcode::{Action, Call, IntcodeError, OpcodeRegistry, Param, Program, StepResult};
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+mod tests_registry {
+    use super::*;
+
+    #[test]
+    fn test_print_debug() {
+        let printed = Arc::new(Mutex::new(vec![]));
+        let log = printed.clone();
+        let mut registry = OpcodeRegistry::new();
+        registry
+            .register(50, "PRN", &[Param::Read], move |call| {
+                log.lock().unwrap().push(*call.arg(0));
+                Ok(Action::Continue)
+            })
+            .unwrap();
+
+        let mut program = Program::new("150,42,50,5,99,7", &[])
+            .unwrap()
+            .with_opcodes(registry);
+        assert_eq!(
+            program.current_instruction().unwrap().to_string(),
+            "PRN #42"
+        );
+        program.run().unwrap();
+        assert_eq!(*printed.lock().unwrap(), [42, 7]);
+    }
+
+    #[test]
+    fn test_halt_with_code_and_writes() {
+        let mut registry = OpcodeRegistry::new();
+        registry
+            .register(60, "EXIT", &[Param::Read], |call| {
+                Ok(Action::Exit(*call.arg(0)))
+            })
+            .unwrap();
+        registry
+            .register(70, "SQR", &[Param::Read, Param::Write], |call| {
+                let square = call.arg(0) * call.arg(0);
+                call.write(1, square);
+                Ok(Action::Continue)
+            })
+            .unwrap();
+
+        let mut program = Program::new("1170,9,7,4,7,160,3,0", &[])
+            .unwrap()
+            .with_opcodes(registry);
+        let (outputs, step) = program.run_until_blocked_or_done().unwrap();
+        assert_eq!(outputs, [81]);
+        assert_eq!(step, StepResult::Halted);
+        assert_eq!(program.exit_code(), Some(&3));
+    }
+
+    #[test]
+    fn test_handler_errors() {
+        let mut registry = OpcodeRegistry::new();
+        registry
+            .register(80, "FAIL", &[], |_| Err(String::from("no")))
+            .unwrap();
+        let mut program = Program::new("1101,1,1,5,80,0", &[])
+            .unwrap()
+            .with_opcodes(registry);
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::Extension {
+                ip: 4,
+                opcode: 80,
+                message: String::from("no")
+            })
+        );
+
+        let mut program = Program::new("80", &[]).unwrap();
+        assert_eq!(
+            program.run(),
+            Err(IntcodeError::InvalidOpcode { ip: 0, opcode: 80 })
+        );
+    }
+
+    #[test]
+    fn test_register_errors() {
+        let mut registry = OpcodeRegistry::<i128>::new();
+        let nop = |_: &mut Call<'_, i128>| Ok(Action::Continue);
+        assert!(registry.register(2, "MUL2", &[], nop).is_err());
+        assert!(registry.register(100, "BIG", &[], nop).is_err());
+        assert!(registry
+            .register(40, "WIDE", &[Param::Read; 4], nop)
+            .is_err());
+        assert!(registry.register(40, "NOP", &[], nop).is_ok());
+        assert_eq!(
+            registry.register(40, "NOP", &[], nop),
+            Err(IntcodeError::Register {
+                opcode: 40,
+                message: String::from("already registered")
+            })
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use intcode::{Memory, PagedMemory, Program, StepResult};
+
+#[cfg(test)]
+mod tests_snapshot {
+    use super::*;
+
+    const QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+
+    #[test]
+    fn test_restore_replays_identically() {
+        let mut program = Program::new(QUINE, &[])
+            .unwrap()
+            .with_memory::<PagedMemory>();
+        let (first, _) = program.run_until_outputs(5).unwrap();
+        assert_eq!(first, [109, 1, 204, -1, 1001]);
+
+        let snapshot = program.snapshot();
+        let rest = program.run().unwrap();
+        let memory = program.memory().to_vec();
+
+        program.restore(&snapshot);
+        assert_eq!(program.run().unwrap(), rest);
+        assert_eq!(program.memory().to_vec(), memory);
+
+        let mut full = first;
+        full.extend(rest);
+        assert_eq!(full, Program::new(QUINE, &[]).unwrap().run().unwrap());
+    }
+
+    #[test]
+    fn test_branches_from_snapshot() {
+        // Outputs 1 for an input of 8 and 0 otherwise.
+        let mut program = Program::new("3,9,8,9,10,9,4,9,99,-1,8", &[])
+            .unwrap()
+            .with_memory::<PagedMemory>();
+        let snapshot = program.snapshot();
+        for (input, expected) in [(8, 1), (3, 0), (8, 1)].iter() {
+            program.restore(&snapshot);
+            program.send_input(*input);
+            let (outputs, state) = program.run_until_blocked_or_done().unwrap();
+            assert_eq!(outputs, [*expected]);
+            assert_eq!(state, StepResult::Halted);
+        }
+        assert_eq!(snapshot.memory().get(9), -1);
+    }
+
+    #[test]
+    fn test_paged_memory_copy_on_write() {
+        let mut memory = PagedMemory::from_cells((0..1000).collect());
+        let snapshot = memory.clone();
+        assert_eq!(memory.shared_pages(&snapshot), 4);
+
+        memory.set(300, -1);
+        assert_eq!(memory.get(300), -1);
+        assert_eq!(snapshot.get(300), 300);
+        assert_eq!(memory.shared_pages(&snapshot), 3);
+
+        memory.set(5000, 1);
+        assert_eq!(memory.len(), 5001);
+        assert_eq!(snapshot.len(), 1000);
+        assert_eq!(memory.get(4999), 0);
+    }
+}
@@ -0,0 +1,91 @@
+use intcode::{Computer, IntcodeError, Memory, Program, StepResult};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests_state {
+    use super::*;
+
+    // Reads a number and outputs it doubled, until it reads 0.
+    const DOUBLER: &str = "3,15,1006,15,14,1002,15,2,15,4,15,1105,1,0,99,0";
+    const QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("intcode-state-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_resume_exactly() {
+        let path = temp_path("quine");
+        let mut program = Program::new(QUINE, &[]).unwrap();
+        program.run_until_outputs(5).unwrap();
+        program.save_state(&path).unwrap();
+        let rest = program.run().unwrap();
+
+        let mut resumed = Program::load_state(&path).unwrap();
+        assert_eq!(resumed.relative_base(), 5);
+        assert_eq!(resumed.run(), Ok(rest));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_far_writes() {
+        // Stores 7 far past the program, then reads a number and outputs the far cell.
+        let path = temp_path("far");
+        let mut program =
+            Program::new("1101,3,4,100000000000000,3,9,4,100000000000000,99,0", &[]).unwrap();
+        assert_eq!(
+            program.run_until_blocked_or_done().unwrap().1,
+            StepResult::NeedsInput
+        );
+        program.save_state(&path).unwrap();
+
+        let mut resumed: Program = Program::load_state(&path).unwrap();
+        assert_eq!(resumed.memory().sparse_cells(), [(100_000_000_000_000, 7)]);
+        resumed.send_input(1);
+        assert_eq!(resumed.run(), Ok(vec![7]));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_pending_inputs() {
+        let path = temp_path("doubler");
+        let mut program = Program::new(DOUBLER, &[1, 2, 3, 0]).unwrap();
+        program.run_until_outputs(1).unwrap();
+        program.save_state(&path).unwrap();
+
+        let mut resumed = Program::load_state(&path).unwrap();
+        assert_eq!(resumed.num_inputs(), 3);
+        assert_eq!(resumed.ip(), program.ip());
+        assert_eq!(resumed.run(), Ok(vec![4, 6]));
+
+        fs::write(&path, "3,0,99").unwrap();
+        let result: Result<Program, _> = Program::load_state(&path);
+        assert!(matches!(
+            result,
+            Err(IntcodeError::Binary { position: 0, .. })
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_computer_save_and_load() {
+        let path = temp_path("computer");
+        let mut computer = Computer::new(DOUBLER).unwrap();
+        computer.send_ascii("\n");
+        computer.run_until_blocked_or_done().unwrap();
+        computer.save(&path).unwrap();
+
+        computer.send_ascii("\0");
+        let (_, state) = computer.run_until_blocked_or_done().unwrap();
+        assert_eq!(state, StepResult::Halted);
+
+        computer.load(&path).unwrap();
+        computer.send_ascii("!");
+        let (output, state) = computer.run_until_blocked_or_done().unwrap();
+        assert_eq!(output, "B");
+        assert_eq!(state, StepResult::NeedsInput);
+        fs::remove_file(&path).unwrap();
+    }
+}
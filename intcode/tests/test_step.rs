@@ -0,0 +1,53 @@
+use intcode::{Memory, Program, StepResult};
+
+#[cfg(test)]
+mod tests_step {
+    use super::*;
+
+    #[test]
+    fn test_execute() {
+        let mut program = Program::new("3,0,4,0,99", &[]).unwrap();
+        assert_eq!(program.execute().unwrap(), StepResult::NeedsInput);
+        program.send_input(7);
+        assert_eq!(program.execute().unwrap(), StepResult::Continued);
+        assert_eq!(program.execute().unwrap(), StepResult::Output(7));
+        assert_eq!(program.execute().unwrap(), StepResult::Halted);
+        assert_eq!(program.execute().unwrap(), StepResult::Halted);
+    }
+
+    #[test]
+    fn test_run_until_blocked_or_done() {
+        let mut program = Program::new("104,1,3,0,4,0,99", &[]).unwrap();
+        let (outputs, state) = program.run_until_blocked_or_done().unwrap();
+        assert_eq!(outputs, [1]);
+        assert_eq!(state, StepResult::NeedsInput);
+
+        program.send_input(2);
+        let (outputs, state) = program.run_until_blocked_or_done().unwrap();
+        assert_eq!(outputs, [2]);
+        assert_eq!(state, StepResult::Halted);
+    }
+
+    #[test]
+    fn test_run_until_outputs() {
+        let mut program = Program::new("104,1,104,2,104,3,99", &[]).unwrap();
+        let (outputs, state) = program.run_until_outputs(2).unwrap();
+        assert_eq!(outputs, [1, 2]);
+        assert_eq!(state, StepResult::Output(2));
+
+        let (outputs, state) = program.run_until_outputs(2).unwrap();
+        assert_eq!(outputs, [3]);
+        assert_eq!(state, StepResult::Halted);
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut program = Program::new("1101,1,2,0,104,5,99", &[]).unwrap();
+        let (outputs, state) = program
+            .run_until(|step| *step == StepResult::Continued)
+            .unwrap();
+        assert!(outputs.is_empty());
+        assert_eq!(state, StepResult::Continued);
+        assert_eq!(program.memory().get(0), 3);
+    }
+}
@@ -0,0 +1,169 @@
+use intcode::{assemble_words, Expr, Goal, IntcodeError, Method, Stuck, SymbolicProgram};
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+mod tests_symbolic {
+    use super::*;
+
+    fn program(source: &str) -> SymbolicProgram {
+        SymbolicProgram::new(assemble_words(source).unwrap())
+    }
+
+    #[test]
+    fn test_expressions() {
+        let source = "
+                    IN [x]
+                    IN [y]
+                    MUL [x], #3, [t]
+                    ADD [t], [y], [t]
+                    ADD [t], #0, [t]
+                    EQ [t], #7, [flag]
+                    OUT [t]
+                    OUT [flag]
+                    HALT
+            x:      db 0
+            y:      db 0
+            t:      db 0
+            flag:   db 0
+        ";
+        let outcome = program(source)
+            .symbolic_input("x", 0..=9)
+            .symbolic_input("y", 0..=9)
+            .run()
+            .unwrap();
+        assert_eq!(outcome.outputs[0].to_string(), "x * 3 + y");
+        assert_eq!(outcome.outputs[1].to_string(), "x * 3 + y == 7");
+        let linear = outcome.outputs[0].linear().unwrap();
+        assert_eq!(linear.constant, 0);
+        assert_eq!(
+            linear.terms.into_iter().collect::<Vec<_>>(),
+            [("x".to_string(), 3), ("y".to_string(), 1)]
+        );
+        assert!(outcome.outputs[1].linear().is_none());
+
+        let values = vec![("x".to_string(), 2), ("y".to_string(), 1)]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(outcome.outputs[1].eval(&values), Some(1));
+
+        // Reading through an address that is itself a symbol.
+        let outcome = SymbolicProgram::new(vec![1, 0, 0, 0, 99])
+            .symbol(1, "p", 0..=4)
+            .run()
+            .unwrap();
+        assert_eq!(outcome.memory[&0].to_string(), "mem[p] + 1");
+        assert_eq!(outcome.memory[&0].eval(&BTreeMap::new()), None);
+
+        // A write far past the program keeps memory sparse.
+        let outcome = SymbolicProgram::new(vec![1101, 1, 1, 100_000_000_000_000, 99])
+            .run()
+            .unwrap();
+        assert_eq!(outcome.memory.len(), 6);
+        assert_eq!(
+            outcome.value(Goal::Memory(100_000_000_000_000)),
+            Some(Expr::Const(2))
+        );
+    }
+
+    #[test]
+    fn test_solve_linear() {
+        // mem[0] = noun * 100 + verb + 7, with noun and verb at 1 and 2 as in day 2,
+        // where the first instruction also reads through them as addresses.
+        let words = vec![
+            1, 0, 0, 3, // mem[3] = mem[noun] + mem[verb]
+            1002, 1, 100, 0, // mem[0] = noun * 100
+            1, 0, 2, 0, // mem[0] += verb
+            1001, 0, 7, 0, // mem[0] += 7
+            99,
+        ];
+        let program = SymbolicProgram::new(words)
+            .symbol(1, "noun", 0..=99)
+            .symbol(2, "verb", 0..=99);
+        let solution = program.solve(Goal::Memory(0), 4213).unwrap();
+        assert_eq!(solution.method, Method::Linear);
+        assert_eq!(solution.values["noun"], 42);
+        assert_eq!(solution.values["verb"], 6);
+
+        // Out of the domains.
+        assert_eq!(program.solve(Goal::Memory(0), 10_007), None);
+        assert_eq!(program.solve(Goal::Output(0), 7), None);
+    }
+
+    #[test]
+    fn test_solve_evaluated() {
+        let source = "
+                    IN [x]
+                    IN [y]
+                    MUL [x], [y], [t]
+                    OUT [t]
+                    HALT
+            x:      db 0
+            y:      db 0
+            t:      db 0
+        ";
+        let solution = program(source)
+            .symbolic_input("x", 0..=10)
+            .symbolic_input("y", 0..=10)
+            .solve(Goal::Output(0), 42)
+            .unwrap();
+        assert_eq!(solution.method, Method::Evaluated);
+        assert_eq!(solution.values["x"], 6);
+        assert_eq!(solution.values["y"], 7);
+    }
+
+    #[test]
+    fn test_stuck_and_enumerated() {
+        // Outputs 100 for x below 5 and x otherwise.
+        let source = "
+                    IN [x]
+                    LT [x], #5, [flag]
+                    JT [flag], #small
+                    OUT [x]
+                    HALT
+            small:  OUT #100
+                    HALT
+            x:      db 0
+            flag:   db 0
+        ";
+        let program = program(source).symbolic_input("x", 0..=9);
+        assert_eq!(program.run(), Err(Stuck::Jump { ip: 6 }));
+        let solution = program.solve(Goal::Output(0), 7).unwrap();
+        assert_eq!(solution.method, Method::Enumerated);
+        assert_eq!(solution.values["x"], 7);
+        assert_eq!(program.solve(Goal::Output(0), 3), None);
+
+        // Reads the next opcode, then an address to write to.
+        assert_eq!(
+            SymbolicProgram::new(vec![3, 2])
+                .symbolic_input("a", 0..=9)
+                .run(),
+            Err(Stuck::Opcode { ip: 2 })
+        );
+        assert_eq!(
+            SymbolicProgram::new(vec![3, 5, 1101, 1, 1, 0, 99])
+                .symbolic_input("a", 0..=9)
+                .run(),
+            Err(Stuck::Write { ip: 2 })
+        );
+        assert_eq!(
+            SymbolicProgram::new(vec![3, 0]).run(),
+            Err(Stuck::Error(IntcodeError::MissingInput {
+                ip: 0,
+                opcode: 3
+            }))
+        );
+
+        // Writes an ADD just below usize::MAX and jumps to it; its operands don't fit.
+        let end = usize::MAX as i128 - 2;
+        let program =
+            SymbolicProgram::new(vec![1101, 1100, 1, end, 1105, 1, end, 0]).symbol(7, "a", 0..=1);
+        assert_eq!(
+            program.run(),
+            Err(Stuck::Error(IntcodeError::Overflow {
+                ip: usize::MAX - 2,
+                opcode: 1101
+            }))
+        );
+        assert_eq!(program.solve(Goal::Output(0), 1), None);
+    }
+}
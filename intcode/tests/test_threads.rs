@@ -0,0 +1,71 @@
+use intcode::{
+    Event, IntcodeError, Message, Packets, Pipe, Program, Ring, ThreadedNetwork, VecMemory,
+};
+use std::time::Duration;
+
+#[cfg(test)]
+mod tests_threads {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(20);
+    const FEEDBACK: &str =
+        "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+    // Reads until it gets something other than -1, then halts.
+    const POLLER: &str = "3,12,1008,12,-1,13,1005,13,0,99,0,0,0,0";
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn test_programs_are_send() {
+        assert_send::<Program>();
+        assert_send::<Program<Pipe, Pipe, VecMemory<i64>>>();
+    }
+
+    #[test]
+    fn test_ring() {
+        let amps = [9, 8, 7, 6, 5]
+            .iter()
+            .map(|&phase| Program::new(FEEDBACK, &[phase]).unwrap())
+            .collect();
+        let mut network = ThreadedNetwork::spawn(amps, Ring, TIMEOUT);
+        network.send(0, &[0]);
+        assert_eq!(network.next_event(), Ok(Event::Halted));
+        assert_eq!(network.last_output(4), Some(139629729));
+    }
+
+    #[test]
+    fn test_packets_and_idle() {
+        let machines = vec![
+            Program::new("104,2,104,42,104,43,99", &[]).unwrap(),
+            Program::new(POLLER, &[]).unwrap(),
+            Program::new("3,100,3,101,104,255,4,100,4,101,99", &[]).unwrap(),
+        ];
+        let mut network = ThreadedNetwork::spawn_polling(machines, Packets::new(3), TIMEOUT, -1);
+        assert_eq!(
+            network.next_event(),
+            Ok(Event::Message(Message {
+                from: 2,
+                to: 255,
+                values: vec![42, 43]
+            }))
+        );
+        assert_eq!(network.next_event(), Ok(Event::Idle));
+        network.send(1, &[7]);
+        assert_eq!(network.next_event(), Ok(Event::Halted));
+    }
+
+    #[test]
+    fn test_errors_and_shutdown() {
+        let machines = vec![
+            Program::new("1105,1,0", &[]).unwrap(),
+            Program::new("104,1,77", &[]).unwrap(),
+        ];
+        let mut network = ThreadedNetwork::spawn(machines, Ring, TIMEOUT);
+        assert_eq!(
+            network.next_event(),
+            Err(IntcodeError::InvalidOpcode { ip: 2, opcode: 77 })
+        );
+        // Machine 0 never stops by itself.
+        network.shutdown();
+    }
+}
@@ -0,0 +1,96 @@
+use intcode::{
+    parse_text, read_program, replay, write_binary, Program, Trace, TraceEntry, TraceSink,
+    TraceWriter,
+};
+use std::env;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+mod tests_trace {
+    use super::*;
+
+    // Outputs 1 for an input of 8 and 0 otherwise.
+    const EQUALS_8: &str = "3,9,8,9,10,9,4,9,99,-1,8";
+
+    fn record(program_string: &str, inputs: &[i128]) -> Trace {
+        let trace = Arc::new(Mutex::new(Trace::new()));
+        let mut program = Program::new(program_string, inputs)
+            .unwrap()
+            .with_trace(trace.clone());
+        program.run().unwrap();
+        let trace = trace.lock().unwrap().clone();
+        trace
+    }
+
+    #[test]
+    fn test_records_every_instruction() {
+        let trace = record(EQUALS_8, &[8]);
+        assert_eq!(
+            trace.to_string(),
+            "0 3 9 w9=8 i8\n2 8 8,8,9 w9=1\n6 4 1 o1\n8 99\n"
+        );
+        assert_eq!(trace.inputs(), [8]);
+        assert_eq!(trace.outputs(), [1]);
+        assert_eq!(
+            trace.entries()[2],
+            TraceEntry {
+                ip: 6,
+                opcode: 4,
+                params: vec![1],
+                write: None,
+                input: None,
+                output: Some(1),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_round_trips() {
+        let trace = record("109,5,21101,-3,4,5,204,5,99", &[]);
+        assert_eq!(Trace::parse(&trace.to_string()).unwrap(), trace);
+        assert!(Trace::parse("0 3 9 w9").is_err());
+        assert!(Trace::parse("x 99").is_err());
+    }
+
+    #[test]
+    fn test_writer_streams_lines() {
+        let writer = Arc::new(Mutex::new(TraceWriter::new(vec![])));
+        let mut program = Program::new(EQUALS_8, &[3])
+            .unwrap()
+            .with_trace(writer.clone());
+        assert_eq!(program.run().unwrap(), [0]);
+        let mut writer = writer.lock().unwrap();
+        writer.flush().unwrap();
+        let text = String::from_utf8(writer.get_ref().clone()).unwrap();
+        assert_eq!(text, "0 3 9 w9=3 i3\n2 8 3,8,9 w9=0\n6 4 0 o0\n8 99\n");
+    }
+
+    #[test]
+    fn test_replay_and_divergence() {
+        let trace = record(EQUALS_8, &[8]);
+        let cells = parse_text(EQUALS_8).unwrap();
+        assert_eq!(replay(cells.clone(), &trace).unwrap(), None);
+
+        // The same program saved in binary.
+        let path = env::temp_dir().join(format!("intcode-trace-{}", std::process::id()));
+        write_binary(&path, &cells).unwrap();
+        assert_eq!(replay(read_program(&path).unwrap(), &trace).unwrap(), None);
+        fs::remove_file(&path).unwrap();
+
+        // Comparing against 7 instead of 8 changes the write at step 1.
+        let changed = parse_text("3,9,8,9,10,9,4,9,99,-1,7").unwrap();
+        let divergence = replay(changed, &trace).unwrap().unwrap();
+        assert_eq!(divergence.index, 1);
+        assert_eq!(divergence.left.unwrap().write, Some((9, 1)));
+        assert_eq!(divergence.right.unwrap().write, Some((9, 0)));
+
+        let mut shorter = Trace::new();
+        for entry in trace.entries()[..2].iter() {
+            shorter.record(entry);
+        }
+        let divergence = trace.first_divergence(&shorter).unwrap();
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.right, None);
+    }
+}
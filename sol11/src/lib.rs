@@ -1,4 +1,4 @@
-use intcode::Program;
+use intcode::{Program, StepResult};
 use std::collections::HashMap;
 use std::env;
 use std::fmt;
@@ -136,31 +136,24 @@ impl Robot {
     fn paint(&mut self) {
         self.send_input();
 
-        let mut output_is_paint = true;
         loop {
-            let (halt, output) = self.program.execute();
-            if halt {
-                break;
+            let (outputs, state) = self.program.run_until_outputs(2).unwrap();
+            match state {
+                StepResult::Halted => break,
+                StepResult::NeedsInput => panic!("Robot asked for input mid-move: {:?}", outputs),
+                _ => {}
             }
-            if output.is_some() {
-                let output = output.unwrap();
-                if output_is_paint {
-                    let paint = match output {
-                        0 => PaintStatus::BLACK,
-                        1 => PaintStatus::WHITE,
-                        _ => panic!("Invalid output {}", output),
-                    };
-                    self.map.insert(self.position, paint);
-                    output_is_paint = false;
-                } else {
-                    self.move_dir(output);
-                    output_is_paint = true;
-                    if self.debug {
-                        println!("{}", self);
-                    }
-                    self.send_input();
-                }
+            let paint = match outputs[0] {
+                0 => PaintStatus::BLACK,
+                1 => PaintStatus::WHITE,
+                _ => panic!("Invalid output {}", outputs[0]),
+            };
+            self.map.insert(self.position, paint);
+            self.move_dir(outputs[1]);
+            if self.debug {
+                println!("{}", self);
             }
+            self.send_input();
         }
     }
 
@@ -173,7 +166,7 @@ impl Robot {
 }
 
 pub fn paint(program_str: String) -> Robot {
-    let program = Program::new(&program_str[..], &vec![]);
+    let program = Program::new(&program_str[..], &vec![]).unwrap();
     let mut robot = Robot::new(program);
     robot.paint();
     robot
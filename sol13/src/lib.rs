@@ -1,4 +1,4 @@
-use intcode::Program;
+use intcode::{Program, StepResult};
 use std::collections::HashMap;
 use std::env;
 use std::fmt;
@@ -60,7 +60,7 @@ impl fmt::Display for Game {
 }
 impl Game {
     fn new(program_str: &str) -> Self {
-        let program = Program::new(program_str, &vec![]);
+        let program = Program::new(program_str, &vec![]).unwrap();
         let screen = HashMap::new();
         Game {
             program,
@@ -99,7 +99,7 @@ impl Game {
 
     fn run(&mut self) {
         loop {
-            let (outputs, done) = self.program.run_until_blocked_or_done();
+            let (outputs, state) = self.program.run_until_blocked_or_done().unwrap();
 
             let mut i = 0;
             while i < outputs.len() {
@@ -129,7 +129,7 @@ impl Game {
             }
             println!("{}", self);
 
-            if done {
+            if state == StepResult::Halted {
                 break;
             }
 
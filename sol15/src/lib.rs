@@ -1,4 +1,5 @@
-use intcode::Program;
+use intcode::{PagedMemory, Program, Snapshot};
+use std::collections::VecDeque;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::slice::Iter;
@@ -34,7 +35,8 @@ pub struct Droid {
     map: HashMap<(i128, i128), Tile>,
     position: (i128, i128),
     visited: HashSet<(i128, i128)>,
-    program_states: HashMap<(i128, i128), Program>,
+    program: Program<VecDeque<i128>, (), PagedMemory>,
+    program_states: HashMap<(i128, i128), Snapshot<PagedMemory>>,
     oxygen_map: HashSet<(i128, i128)>,
     pub paths: HashMap<(i128, i128), Vec<(i128, i128)>>,
 }
@@ -107,20 +109,23 @@ impl fmt::Display for Droid {
 }
 impl Droid {
     fn new(program_str: &str) -> Self {
-        let program = Program::new(program_str, &vec![]);
+        let program = Program::new(program_str, &vec![])
+            .unwrap()
+            .with_memory::<PagedMemory>();
         let mut map = HashMap::new();
         let position = (0, 0);
         map.insert(position, Tile::VISITED);
         let mut visited = HashSet::new();
         visited.insert(position);
         let mut program_states = HashMap::new();
-        program_states.insert(position, program);
+        program_states.insert(position, program.snapshot());
         let mut paths = HashMap::new();
         paths.insert(position, vec![]);
         Droid {
             map,
             position: position,
             visited,
+            program,
             program_states,
             paths,
             oxygen_map: HashSet::new(),
@@ -160,7 +165,8 @@ impl Droid {
                     {
                         continue;
                     }
-                    let mut program = self.program_states.get(&position).unwrap().clone();
+                    let program = &mut self.program;
+                    program.restore(self.program_states.get(&position).unwrap());
                     let input = match direction {
                         Direction::NORTH => 1,
                         Direction::WEST => 3,
@@ -169,8 +175,8 @@ impl Droid {
                     };
                     program.send_input(input);
 
-                    let (outputs, done) = program.run_until_blocked_or_done();
-                    if done {
+                    let (outputs, state) = program.run_until_blocked_or_done().unwrap();
+                    if state.is_halted() {
                         panic!("Hit unexpected halt");
                     }
                     assert_eq!(outputs.len(), 1, "Invalid outputs: {:?}", outputs);
@@ -180,12 +186,12 @@ impl Droid {
                     } else if output == 1 {
                         self.map.insert(next_position, Tile::VISITED);
                         new_horizon.push(next_position);
-                        self.program_states.insert(next_position, program.clone());
+                        self.program_states.insert(next_position, program.snapshot());
                         self.add_path(position, next_position);
                     } else if output == 2 {
                         self.map.insert(next_position, Tile::DEST);
                         new_horizon.push(next_position);
-                        self.program_states.insert(next_position, program.clone());
+                        self.program_states.insert(next_position, program.snapshot());
                         self.add_path(position, next_position);
                     } else {
                         panic!("Invalid output: {}", output);
@@ -64,7 +64,7 @@ impl fmt::Display for Robot {
 }
 impl Robot {
     fn new(program_str: &str) -> Self {
-        let program = Program::new(program_str, &vec![]);
+        let program = Program::new(program_str, &vec![]).unwrap();
         Robot {
             program,
             camera: vec![],
@@ -78,7 +78,7 @@ impl Robot {
     }
 
     fn fill_map(&mut self) {
-        let outputs = self.program.run();
+        let outputs = self.program.run().unwrap();
         let mut rows = vec![];
         let mut row = vec![];
         let mut count = 0;
@@ -161,7 +161,7 @@ impl Robot {
         self.program.send_input('n' as i128);
         self.program.send_input('\n' as i128);
 
-        let output = self.program.run();
+        let output = self.program.run().unwrap();
         *output.iter().last().unwrap()
     }
 }
@@ -15,8 +15,8 @@ fn get_output(
     if let Some(o) = cache.get(&(x, y)) {
         *o
     } else {
-        let mut program = Program::new(program_str, &vec![x as i128, y as i128]);
-        let o = program.run_until_blocked_or_done().0[0];
+        let mut program = Program::new(program_str, &vec![x as i128, y as i128]).unwrap();
+        let o = program.run_until_blocked_or_done().unwrap().0[0];
         cache.insert((x, y), o);
         o
     }
@@ -6,7 +6,7 @@ pub struct Droid {
 
 impl Droid {
     fn new(program_str: &str) -> Self {
-        let computer = Computer::new(program_str);
+        let computer = Computer::new(program_str).unwrap();
         Droid {
             computer
         }
@@ -15,7 +15,7 @@ impl Droid {
 
     fn run_interactive(&mut self) {
         loop {
-            self.computer.run_interactive();
+            self.computer.run_interactive().unwrap();
             self.computer.reset();
         }
     }
@@ -6,5 +6,5 @@ fn main() {
     let file_string = fs::read_to_string("input.txt").unwrap();
     let file_string = file_string.trim();
 
-    process_program(file_string, &[5]); 
+    process_program(file_string, &[5]).unwrap();
 }
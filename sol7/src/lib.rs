@@ -1,41 +1,17 @@
-use intcode::Program;
+use intcode::{Event, Network, Program, Ring};
 use permutohedron::Heap;
-use std::cell::RefCell;
 
 const NUM_AMPS: usize = 5;
 
 fn get_signal_for_inputs(program_str: &str, inputs: &[i128; 5]) -> i128 {
-    let programs: Vec<RefCell<Program>> = (0..NUM_AMPS)
-        .map(|i| RefCell::new(Program::new(program_str, &vec![inputs[i]])))
+    let amps = inputs
+        .iter()
+        .map(|&phase| Program::new(program_str, &[phase]).unwrap())
         .collect();
-    let mut program_index = 0;
-    let mut output = 0;
-    let mut halted = vec![];
-
-    loop {
-        let mut cur_program = programs[program_index].borrow_mut();
-        if halted.contains(&program_index) {
-            program_index = (program_index + 1) % NUM_AMPS;
-            continue;
-        }
-
-        cur_program.send_input(output);
-
-        loop {
-            let (halt, new_output) = cur_program.execute();
-            if halt && program_index == NUM_AMPS - 1 {
-                return output;
-            } else if halt {
-                halted.push(program_index);
-                break;
-            } else if new_output.is_some() {
-                output = new_output.unwrap();
-                break;
-            }
-        }
-
-        program_index = (program_index + 1) % NUM_AMPS;
-    }
+    let mut network = Network::new(amps, Ring);
+    network.send(0, &[0]);
+    while network.next_event().unwrap() != Event::Halted {}
+    *network.last_output(NUM_AMPS - 1).unwrap()
 }
 
 pub fn get_signal(program_str: &str) -> i128 {
@@ -5,9 +5,9 @@ fn main() {
     let file_string = fs::read_to_string("input.txt").unwrap();
     let file_string = file_string.trim();
 
-    let (_p, o) = process_program(file_string, &vec![1]);
+    let (_p, o) = process_program(file_string, &vec![1]).unwrap();
     println!("{:?}", o);
 
-    let (_p, o) = process_program(file_string, &vec![2]);
+    let (_p, o) = process_program(file_string, &vec![2]).unwrap();
     println!("{:?}", o);
 }
@@ -1,7 +1,13 @@
 use std::fs;
+use std::path::Path;
 
 pub fn get_file_string() -> String {
-    match fs::read_to_string("input.txt") {
+    get_file_string_from("input.txt")
+}
+
+/// Like `get_file_string`, for an input somewhere other than `input.txt` in the CWD.
+pub fn get_file_string_from<P: AsRef<Path>>(path: P) -> String {
+    match fs::read_to_string(path) {
         Ok(s) => String::from(s.trim()),
         _ => panic!("Make sure to paste in input!"),
     }